@@ -1,47 +1,149 @@
 use wasm_bindgen::prelude::*;
-use aes_gcm::{Aes256Gcm, Key, Nonce};
-use aes_gcm::aead::{Aead, NewAead};
 use hex::encode;
+use echo_crypto::EchoError;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+// The AES-GCM primitives live in echo-crypto, this crate only exposes them to JavaScript
+pub use echo_crypto::aead::{
+    aes_gcm_decrypt, aes_gcm_encrypt, message_header_bytes, open_envelope, seal_envelope, ENVELOPE_VERSION,
+    MESSAGE_HEADER_VERSION, NONCE_LENGTH,
+};
 
 #[wasm_bindgen]
 // This function encrypts a given text using AES-GCM with a 256-bit key and a 96-bit nonce
 pub fn encrypt(text: &str, key: &[u8], nonce: &[u8]) -> Result<String, JsValue> {
-    if key.len() != 32 {
-        return Err(JsValue::from_str("Invalid key length"));
-    }
-
-    if nonce.len() != 12 {
-        return Err(JsValue::from_str("Invalid nonce length"));
-    }
+    encrypt_with_aad(text, key, nonce, &[])
+}
 
-    let key = Key::from_slice(key);
-    let cipher = Aes256Gcm::new(key);
+// This function decrypts a given ciphertext using AES-GCM with a 256-bit key and a 96-bit nonce
+#[wasm_bindgen]
+pub fn decrypt(text: &str, key: &[u8], nonce: &[u8]) -> Result<String, JsValue> {
+    decrypt_with_aad(text, key, nonce, &[])
+}
 
-    let nonce = Nonce::from_slice(nonce); 
-    let ciphertext = cipher.encrypt(nonce, text.as_bytes())
-        .map_err(|_| JsValue::from_str("Encryption failed"))?;
+#[wasm_bindgen]
+// This function encrypts a given text using AES-GCM and authenticates the associated data alongside it
+// The same associated data must be given to decrypt_with_aad, otherwise decryption fails
+pub fn encrypt_with_aad(text: &str, key: &[u8], nonce: &[u8], aad: &[u8]) -> Result<String, JsValue> {
+    let ciphertext = encrypt_bytes(text.as_bytes(), key, nonce, aad)?;
 
     Ok(encode(ciphertext))
 }
 
-// This function decrypts a given ciphertext using AES-GCM with a 256-bit key and a 96-bit nonce
 #[wasm_bindgen]
-pub fn decrypt(text: &str, key: &[u8], nonce: &[u8]) -> Result<String, JsValue> {
-    if key.len() != 32 {
-        return Err(JsValue::from_str("Invalid key length"));
+// This function decrypts a given ciphertext using AES-GCM and checks it against the associated data
+pub fn decrypt_with_aad(text: &str, key: &[u8], nonce: &[u8], aad: &[u8]) -> Result<String, JsValue> {
+    let ciphertext = hex::decode(text).map_err(|_| EchoError::InvalidEncoding)?;
+    let decrypted_text = decrypt_bytes(&ciphertext, key, nonce, aad)?;
+
+    String::from_utf8(decrypted_text).map_err(|_| EchoError::InvalidEncoding.into())
+}
+
+#[wasm_bindgen]
+// This function encrypts raw bytes (images, files, voice notes) and returns the raw ciphertext with the tag appended
+pub fn encrypt_bytes(plaintext: &[u8], key: &[u8], nonce: &[u8], aad: &[u8]) -> Result<Vec<u8>, JsValue> {
+    aes_gcm_encrypt(plaintext, key, nonce, aad).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+// This function decrypts raw ciphertext bytes, the output is not required to be valid UTF-8
+pub fn decrypt_bytes(ciphertext: &[u8], key: &[u8], nonce: &[u8], aad: &[u8]) -> Result<Vec<u8>, JsValue> {
+    aes_gcm_decrypt(ciphertext, key, nonce, aad).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+// This function encodes the socket payload header so it can be passed as associated data
+pub fn encode_message_header(sender_id: &str, recipient_id: &str, message_number: u32, ephemeral_key: &[u8]) -> Result<Vec<u8>, JsValue> {
+    message_header_bytes(sender_id, recipient_id, message_number, ephemeral_key).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+// This function encrypts a given text with AES-GCM and returns the hex encoded envelope (version || nonce || ciphertext)
+pub fn seal(text: &str, key: &[u8]) -> Result<String, JsValue> {
+    seal_with_aad(text, key, &[])
+}
+
+#[wasm_bindgen]
+// This function decrypts a hex encoded envelope produced by seal
+pub fn open(envelope: &str, key: &[u8]) -> Result<String, JsValue> {
+    open_with_aad(envelope, key, &[])
+}
+
+#[wasm_bindgen]
+// This function works like seal but also authenticates the associated data (e.g. an encoded message header)
+pub fn seal_with_aad(text: &str, key: &[u8], aad: &[u8]) -> Result<String, JsValue> {
+    let envelope = seal_envelope(text.as_bytes(), key, aad)?;
+
+    Ok(encode(envelope))
+}
+
+#[wasm_bindgen]
+// This function decrypts a hex encoded envelope produced by seal_with_aad
+pub fn open_with_aad(envelope: &str, key: &[u8], aad: &[u8]) -> Result<String, JsValue> {
+    let envelope = hex::decode(envelope).map_err(|_| EchoError::InvalidEncoding)?;
+    let decrypted_text = open_envelope(&envelope, key, aad)?;
+
+    String::from_utf8(decrypted_text).map_err(|_| EchoError::InvalidEncoding.into())
+}
+
+#[wasm_bindgen]
+// This function seals raw bytes into an envelope (version || nonce || ciphertext) without any hex encoding
+pub fn seal_bytes(plaintext: &[u8], key: &[u8], aad: &[u8]) -> Result<Vec<u8>, JsValue> {
+    seal_envelope(plaintext, key, aad).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+// This function opens an envelope produced by seal_bytes and returns the raw plaintext bytes
+pub fn open_bytes(envelope: &[u8], key: &[u8], aad: &[u8]) -> Result<Vec<u8>, JsValue> {
+    open_envelope(envelope, key, aad).map_err(JsValue::from)
+}
+
+// This struct is a 256-bit AES-GCM key, it can only be built from exactly 32 bytes
+// The key never leaves wasm memory and is wiped when the handle is freed
+#[wasm_bindgen]
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct SymmetricKey {
+    key: [u8; 32],
+}
+
+#[wasm_bindgen]
+impl SymmetricKey {
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8]) -> Result<SymmetricKey, JsValue> {
+        let key = bytes.try_into().map_err(|_| EchoError::InvalidSymmetricKey)?;
+        Ok(SymmetricKey { key })
     }
 
-    if nonce.len() != 12 {
-        return Err(JsValue::from_str("Invalid nonce length"));
+    // This function generates a new random key
+    pub fn generate() -> Result<SymmetricKey, JsValue> {
+        Ok(SymmetricKey { key: *echo_crypto::aead::generate_key()? })
     }
 
-    let key = Key::from_slice(key);
-    let cipher = Aes256Gcm::new(key);
+    // This function derives the message key from a 32 byte Diffie-Hellman shared secret using HKDF
+    pub fn derive(shared_secret: &[u8]) -> Result<SymmetricKey, JsValue> {
+        let shared_secret = shared_secret
+            .try_into()
+            .map_err(|_| EchoError::InvalidSymmetricKey)?;
+        Ok(SymmetricKey { key: *echo_crypto::kdf::derive_symmetric_key(shared_secret) })
+    }
 
-    let nonce = Nonce::from_slice(nonce); 
-    let ciphertext = hex::decode(text).map_err(|_| JsValue::from_str("Invalid ciphertext"))?;
-    let decrypted_text = cipher.decrypt(nonce, ciphertext.as_ref())
-        .map_err(|_| JsValue::from_str("Decryption failed"))?;
+    // This function works like seal_with_aad with this key
+    pub fn seal(&self, text: &str, aad: &[u8]) -> Result<String, JsValue> {
+        seal_with_aad(text, &self.key, aad)
+    }
+
+    // This function works like open_with_aad with this key
+    pub fn open(&self, envelope: &str, aad: &[u8]) -> Result<String, JsValue> {
+        open_with_aad(envelope, &self.key, aad)
+    }
 
-    String::from_utf8(decrypted_text).map_err(|_| JsValue::from_str("Invalid UTF-8"))
+    // This function works like seal_bytes with this key
+    pub fn seal_bytes(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, JsValue> {
+        seal_bytes(plaintext, &self.key, aad)
+    }
+
+    // This function works like open_bytes with this key
+    pub fn open_bytes(&self, envelope: &[u8], aad: &[u8]) -> Result<Vec<u8>, JsValue> {
+        open_bytes(envelope, &self.key, aad)
+    }
 }
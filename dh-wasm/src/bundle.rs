@@ -0,0 +1,109 @@
+use wasm_bindgen::prelude::*;
+use echo_crypto::EchoError;
+use crate::keys::{SignedPreKey, X25519PublicKey};
+use crate::prekeys::OneTimePreKey;
+
+// This struct holds the public keys a user publishes so others can start a session with them
+// IK is the identity key, SPK the signed prekey with its XEdDSA signature and OPK an optional one-time prekey
+// The binary layout is defined by echo_crypto::PreKeyBundle
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreKeyBundle {
+    inner: echo_crypto::PreKeyBundle,
+}
+
+impl PreKeyBundle {
+    pub fn as_core(&self) -> &echo_crypto::PreKeyBundle {
+        &self.inner
+    }
+}
+
+impl From<echo_crypto::PreKeyBundle> for PreKeyBundle {
+    fn from(inner: echo_crypto::PreKeyBundle) -> PreKeyBundle {
+        PreKeyBundle { inner }
+    }
+}
+
+#[wasm_bindgen]
+impl PreKeyBundle {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        identity_key: &[u8],
+        signed_prekey_id: u32,
+        signed_prekey: &[u8],
+        signed_prekey_signature: &[u8],
+        one_time_prekey_id: Option<u32>,
+        one_time_prekey: Option<Vec<u8>>,
+    ) -> Result<PreKeyBundle, JsValue> {
+        let one_time_prekey = match (one_time_prekey_id, one_time_prekey.as_deref()) {
+            (Some(id), Some(key)) => Some((id, key)),
+            (None, None) => None,
+            _ => return Err(EchoError::MalformedBundle.into()),
+        };
+
+        echo_crypto::PreKeyBundle::from_keys(identity_key, signed_prekey_id, signed_prekey, signed_prekey_signature, one_time_prekey)
+            .map(PreKeyBundle::from)
+            .map_err(JsValue::from)
+    }
+
+    // This function builds the bundle to publish from our own typed keys
+    pub fn create(identity_key: &X25519PublicKey, signed_prekey: &SignedPreKey, one_time_prekey: Option<OneTimePreKey>) -> PreKeyBundle {
+        let inner = echo_crypto::PreKeyBundle::new(
+            *identity_key.as_core(),
+            signed_prekey.as_core(),
+            one_time_prekey.as_ref().map(OneTimePreKey::as_core),
+        );
+        PreKeyBundle { inner }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn identity_public_key(&self) -> X25519PublicKey {
+        self.inner.identity_key.into()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn signed_prekey_public_key(&self) -> X25519PublicKey {
+        self.inner.signed_prekey.into()
+    }
+
+    // This function parses a serialized bundle, see echo_crypto::PreKeyBundle::to_bytes for the layout
+    pub fn deserialize(bytes: &[u8]) -> Result<PreKeyBundle, JsValue> {
+        echo_crypto::PreKeyBundle::from_bytes(bytes)
+            .map(PreKeyBundle::from)
+            .map_err(JsValue::from)
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        self.inner.to_bytes()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn identity_key(&self) -> Vec<u8> {
+        self.inner.identity_key.to_bytes().to_vec()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn signed_prekey_id(&self) -> u32 {
+        self.inner.signed_prekey_id
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn signed_prekey(&self) -> Vec<u8> {
+        self.inner.signed_prekey.to_bytes().to_vec()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn signed_prekey_signature(&self) -> Vec<u8> {
+        self.inner.signed_prekey_signature.to_vec()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn one_time_prekey_id(&self) -> Option<u32> {
+        self.inner.one_time_prekey.map(|(id, _)| id)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn one_time_prekey(&self) -> Option<Vec<u8>> {
+        self.inner.one_time_prekey.map(|(_, key)| key.to_bytes().to_vec())
+    }
+}
@@ -0,0 +1,41 @@
+use wasm_bindgen::prelude::*;
+use crate::keys::X25519PublicKey;
+
+// This struct is the safety number of a conversation as seen by the local user, see echo_crypto::Fingerprint
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fingerprint {
+    inner: echo_crypto::Fingerprint,
+}
+
+#[wasm_bindgen]
+impl Fingerprint {
+    // This function computes the fingerprint from the stable user IDs and identity keys of both users
+    #[wasm_bindgen(constructor)]
+    pub fn new(local_id: &str, local_identity_key: &X25519PublicKey, remote_id: &str, remote_identity_key: &X25519PublicKey) -> Fingerprint {
+        let inner = echo_crypto::Fingerprint::new(
+            local_id.as_bytes(),
+            local_identity_key.as_core(),
+            remote_id.as_bytes(),
+            remote_identity_key.as_core(),
+        );
+        Fingerprint { inner }
+    }
+
+    // 60 digits, identical on both devices, usually shown as twelve groups of five
+    #[wasm_bindgen(getter)]
+    pub fn safety_number(&self) -> String {
+        self.inner.safety_number()
+    }
+
+    // Payload to render as a QR code
+    #[wasm_bindgen(getter)]
+    pub fn scannable(&self) -> Vec<u8> {
+        self.inner.scannable().to_vec()
+    }
+
+    // This function compares a payload scanned from the other user's device
+    pub fn matches_scanned(&self, scanned: &[u8]) -> Result<bool, JsValue> {
+        Ok(self.inner.matches_scanned(scanned)?)
+    }
+}
@@ -0,0 +1,169 @@
+use wasm_bindgen::prelude::*;
+use echo_crypto::{keys, EchoError, KeyPair, PrivateKey, PublicKey};
+
+// This struct is an X25519 public key (identity key, signed prekey, one-time prekey or ephemeral key of a peer)
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct X25519PublicKey {
+    inner: PublicKey,
+}
+
+impl X25519PublicKey {
+    pub fn as_core(&self) -> &PublicKey {
+        &self.inner
+    }
+}
+
+impl From<PublicKey> for X25519PublicKey {
+    fn from(inner: PublicKey) -> X25519PublicKey {
+        X25519PublicKey { inner }
+    }
+}
+
+#[wasm_bindgen]
+impl X25519PublicKey {
+    // This function accepts exactly 32 bytes (the Montgomery u-coordinate)
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8]) -> Result<X25519PublicKey, JsValue> {
+        PublicKey::from_bytes(bytes)
+            .map(X25519PublicKey::from)
+            .map_err(JsValue::from)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.inner.to_bytes().to_vec()
+    }
+}
+
+// This struct is the long term identity key pair
+// The X25519 key shares its scalar with the Ed25519 seed so one seed gives both forms
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct IdentityKeyPair {
+    inner: KeyPair,
+}
+
+impl IdentityKeyPair {
+    pub fn as_core(&self) -> &KeyPair {
+        &self.inner
+    }
+}
+
+#[wasm_bindgen]
+impl IdentityKeyPair {
+    // This function generates a new identity key pair
+    pub fn generate() -> Result<IdentityKeyPair, JsValue> {
+        let inner = KeyPair::generate()?;
+        Ok(IdentityKeyPair { inner })
+    }
+
+    // This function derives the identity key pair from a 32 byte Ed25519 seed
+    pub fn from_ed25519_seed(seed: &[u8]) -> Result<IdentityKeyPair, JsValue> {
+        if seed.len() != 32 {
+            return Err(EchoError::InvalidPrivateKey.into());
+        }
+
+        Ok(IdentityKeyPair { inner: keys::x25519_from_ed25519_seed(seed) })
+    }
+
+    // This function restores an identity key pair from its 32 byte X25519 private key
+    pub fn from_private_key(private_key: &[u8]) -> Result<IdentityKeyPair, JsValue> {
+        let private_key = PrivateKey::from_bytes(private_key)?;
+        let public_key = private_key.public_key();
+        Ok(IdentityKeyPair { inner: KeyPair { private_key, public_key } })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn public_key(&self) -> X25519PublicKey {
+        self.inner.public_key.into()
+    }
+}
+
+// This struct is the medium term signed prekey together with the identity key signature over its public half
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct SignedPreKey {
+    inner: echo_crypto::SignedPreKey,
+}
+
+impl SignedPreKey {
+    pub fn as_core(&self) -> &echo_crypto::SignedPreKey {
+        &self.inner
+    }
+}
+
+#[wasm_bindgen]
+impl SignedPreKey {
+    // This function restores a signed prekey from its ID, 32 byte private key and 64 byte signature
+    #[wasm_bindgen(constructor)]
+    pub fn new(id: u32, private_key: &[u8], signature: &[u8]) -> Result<SignedPreKey, JsValue> {
+        let private_key = PrivateKey::from_bytes(private_key)?;
+        let signature = signature
+            .try_into()
+            .map_err(|_| EchoError::InvalidSignature)?;
+        let public_key = private_key.public_key();
+
+        Ok(SignedPreKey {
+            inner: echo_crypto::SignedPreKey { id, key_pair: KeyPair { private_key, public_key }, signature },
+        })
+    }
+
+    // This function generates a new signed prekey and signs it with the identity key
+    pub fn generate(id: u32, identity: &IdentityKeyPair) -> Result<SignedPreKey, JsValue> {
+        let inner = echo_crypto::SignedPreKey::generate(id, &identity.inner.private_key)?;
+        Ok(SignedPreKey { inner })
+    }
+
+    // This function checks the signature against the owner's identity key
+    pub fn verify(&self, identity_key: &X25519PublicKey) -> bool {
+        self.inner.verify(&identity_key.inner)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn id(&self) -> u32 {
+        self.inner.id
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn public_key(&self) -> X25519PublicKey {
+        self.inner.key_pair.public_key.into()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn signature(&self) -> Vec<u8> {
+        self.inner.signature.to_vec()
+    }
+}
+
+// This struct is the single use ephemeral key pair of an X3DH initiator
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct EphemeralKeyPair {
+    inner: KeyPair,
+}
+
+impl EphemeralKeyPair {
+    pub fn as_core(&self) -> &KeyPair {
+        &self.inner
+    }
+}
+
+#[wasm_bindgen]
+impl EphemeralKeyPair {
+    // This function generates a new ephemeral key pair
+    pub fn generate() -> Result<EphemeralKeyPair, JsValue> {
+        let inner = KeyPair::generate()?;
+        Ok(EphemeralKeyPair { inner })
+    }
+
+    // This function builds an ephemeral key pair from at least 32 random bytes
+    pub fn from_random_bytes(js_random_bytes: &[u8]) -> Result<EphemeralKeyPair, JsValue> {
+        let inner = KeyPair::from_random_bytes(js_random_bytes)?;
+        Ok(EphemeralKeyPair { inner })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn public_key(&self) -> X25519PublicKey {
+        self.inner.public_key.into()
+    }
+}
@@ -0,0 +1,156 @@
+use wasm_bindgen::prelude::*;
+use crate::bundle::PreKeyBundle;
+use crate::keys::X25519PublicKey;
+use crate::trust::TrustedIdentity;
+use ratchet_wasm::RatchetSession;
+
+// This struct is the outcome of X3DH run inside a KeyStore: a handle to SK and the associated data AD = IK_A || IK_B
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StoredX3dh {
+    inner: echo_crypto::StoredX3dh,
+}
+
+#[wasm_bindgen]
+impl StoredX3dh {
+    #[wasm_bindgen(getter)]
+    pub fn secret(&self) -> u32 {
+        self.inner.secret
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn associated_data(&self) -> Vec<u8> {
+        self.inner.associated_data.to_vec()
+    }
+}
+
+// This struct owns every private key of the user inside wasm memory
+// JavaScript only receives public keys and numeric handles, the store itself can only be persisted inside a Vault
+// There is no export under a caller chosen key: any script holding that key could decrypt every private key
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct KeyStore {
+    inner: echo_crypto::KeyStore,
+}
+
+impl KeyStore {
+    pub fn as_core(&self) -> &echo_crypto::KeyStore {
+        &self.inner
+    }
+}
+
+impl From<echo_crypto::KeyStore> for KeyStore {
+    fn from(inner: echo_crypto::KeyStore) -> KeyStore {
+        KeyStore { inner }
+    }
+}
+
+#[wasm_bindgen]
+impl KeyStore {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> KeyStore {
+        KeyStore::default()
+    }
+
+    // This function generates a new identity key pair and returns its public half
+    pub fn generate_identity(&mut self) -> Result<X25519PublicKey, JsValue> {
+        Ok(self.inner.generate_identity()?.into())
+    }
+
+    pub fn identity_public_key(&self) -> Result<X25519PublicKey, JsValue> {
+        Ok(self.inner.identity_public_key()?.into())
+    }
+
+    // This function generates a signed prekey with the given ID and returns its public half
+    pub fn generate_signed_prekey(&mut self, id: u32) -> Result<X25519PublicKey, JsValue> {
+        Ok(self.inner.generate_signed_prekey(id)?.into())
+    }
+
+    // This function generates one-time prekeys and returns their public halves encoded for upload
+    // COUNT (4 bytes BE) || (ID (4 bytes BE) || OPK (32 bytes)) * COUNT
+    pub fn generate_one_time_prekeys(&mut self, start_id: u32, count: u32) -> Result<Vec<u8>, JsValue> {
+        Ok(self.inner.generate_one_time_prekeys(start_id, count)?)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn one_time_prekey_count(&self) -> usize {
+        self.inner.one_time_prekey_count()
+    }
+
+    // This function builds the bundle to publish for a signed prekey and optionally one of the one-time prekeys
+    pub fn prekey_bundle(&self, signed_prekey_id: u32, one_time_prekey_id: Option<u32>) -> Result<PreKeyBundle, JsValue> {
+        Ok(self.inner.prekey_bundle(signed_prekey_id, one_time_prekey_id)?.into())
+    }
+
+    // This function generates an ephemeral key pair and returns its handle
+    pub fn generate_ephemeral_key(&mut self) -> Result<u32, JsValue> {
+        Ok(self.inner.generate_ephemeral_key()?)
+    }
+
+    // This function generates a random AES-256 key and returns its handle
+    pub fn generate_symmetric_key(&mut self) -> Result<u32, JsValue> {
+        Ok(self.inner.generate_symmetric_key()?)
+    }
+
+    // This function returns the public half of an ephemeral key handle
+    pub fn public_key(&self, handle: u32) -> Result<X25519PublicKey, JsValue> {
+        Ok(self.inner.public_key(handle)?.into())
+    }
+
+    // This function derives the message key from an X3DH secret handle and returns the handle of the new key
+    pub fn derive_symmetric_key(&mut self, secret: u32) -> Result<u32, JsValue> {
+        Ok(self.inner.derive_symmetric_key(secret)?)
+    }
+
+    // This function wipes the key behind a handle
+    pub fn remove(&mut self, handle: u32) {
+        self.inner.remove(handle);
+    }
+
+    // This function verifies the bundle's signed prekey and runs X3DH as the initiator, the ephemeral key is consumed
+    // their_identity comes from TrustStore.ensure_trusted and must match the bundle's identity key
+    pub fn x3dh_initiate(&mut self, bundle: &PreKeyBundle, their_identity: &TrustedIdentity, ephemeral: u32) -> Result<StoredX3dh, JsValue> {
+        let inner = self.inner.x3dh_initiate(&bundle.as_core().verify()?, their_identity.as_core(), ephemeral)?;
+        Ok(StoredX3dh { inner })
+    }
+
+    // This function runs X3DH as the responder, the one-time prekey is consumed
+    pub fn x3dh_respond(
+        &mut self,
+        signed_prekey_id: u32,
+        one_time_prekey_id: Option<u32>,
+        their_identity: &TrustedIdentity,
+        their_ephemeral_key: &X25519PublicKey,
+    ) -> Result<StoredX3dh, JsValue> {
+        let inner = self.inner.x3dh_respond(
+            signed_prekey_id,
+            one_time_prekey_id,
+            their_identity.as_core(),
+            their_ephemeral_key.as_core(),
+        )?;
+        Ok(StoredX3dh { inner })
+    }
+
+    // This function starts the Double Ratchet as the initiator from the X3DH result, SK never reaches JavaScript
+    // The remote ratchet key is the signed prekey of the bundle given to x3dh_initiate, the X3DH secret handle is consumed
+    pub fn ratchet_initiate(&mut self, x3dh: &StoredX3dh, header_encryption: bool) -> Result<RatchetSession, JsValue> {
+        let session = self.inner.ratchet_initiate(&x3dh.inner, header_encryption)?;
+        Ok(session.into())
+    }
+
+    // This function starts the Double Ratchet as the responder with the signed prekey given to x3dh_respond
+    pub fn ratchet_respond(&mut self, x3dh: &StoredX3dh, signed_prekey_id: u32, header_encryption: bool) -> Result<RatchetSession, JsValue> {
+        let session = self.inner.ratchet_respond(&x3dh.inner, signed_prekey_id, header_encryption)?;
+        Ok(session.into())
+    }
+
+    // This function seals bytes with the key behind the handle, see echo_crypto::aead::seal_envelope
+    pub fn seal(&self, key: u32, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, JsValue> {
+        Ok(self.inner.seal(key, plaintext, aad)?)
+    }
+
+    // This function opens an envelope with the key behind the handle
+    pub fn open(&self, key: u32, envelope: &[u8], aad: &[u8]) -> Result<Vec<u8>, JsValue> {
+        Ok(self.inner.open(key, envelope, aad)?)
+    }
+}
@@ -1,209 +1,214 @@
 use wasm_bindgen::prelude::*;
-use hkdf::Hkdf;
-use sha2::Sha256;
-use curve25519_dalek::montgomery::MontgomeryPoint;
-use curve25519_dalek::scalar::Scalar;
-use curve25519_dalek::constants::X25519_BASEPOINT;
-
-// For edwards algorithm
-use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
-use sha2::{Sha512, Digest};
+use echo_crypto::{kdf, EchoError, KeyPair, PrivateKey, PublicKey};
 use js_sys::{Object, Uint8Array};
 use wasm_bindgen::JsValue;
 
+pub mod bundle;
+pub mod fingerprint;
+pub mod keys;
+pub mod keystore;
+pub mod prekeys;
+pub mod trust;
+pub mod vault;
+pub mod x3dh;
+
+pub use bundle::PreKeyBundle;
+pub use fingerprint::Fingerprint;
+pub use keys::{EphemeralKeyPair, IdentityKeyPair, SignedPreKey, X25519PublicKey};
+pub use keystore::{KeyStore, StoredX3dh};
+pub use prekeys::{OneTimePreKey, OneTimePreKeyBatch};
+pub use trust::{TrustStatus, TrustStore, TrustedIdentity};
+pub use vault::Vault;
+pub use x3dh::X3dhResult;
+
 #[wasm_bindgen]
 // This function derives a symmetric key from the shared secret using HKDF
-pub fn derive_symmetric_key(shared_secret: &[u8]) -> Vec<u8> {
-    if shared_secret.len() != 32 {
-        return vec![];
-    }
+pub fn derive_symmetric_key(shared_secret: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let shared_secret = shared_secret.try_into().map_err(|_| EchoError::InvalidSymmetricKey)?;
 
-    let hk = Hkdf::<Sha256>::new(None, shared_secret);
-    let mut okm = [0u8; 32];
-    hk.expand(b"message-encryption", &mut okm).unwrap();
-
-    okm.to_vec()
+    Ok(kdf::derive_symmetric_key(shared_secret).to_vec())
 }
 
 #[wasm_bindgen]
 // This function performs the Diffie-Hellman key exchange using X25519
-pub fn diffie_hellman(my_private_key_bytes: &[u8], their_public_key_bytes: &[u8]) -> Vec<u8> {
-    if my_private_key_bytes.len() != 32 || their_public_key_bytes.len() != 32 {
-        return vec![];
-    }
-
-    let mut private_key = [0u8; 32];
-    private_key.copy_from_slice(&my_private_key_bytes[..32]);
-
-    // Clamp private key as per X25519 spec
-    private_key[0] &= 248;
-    private_key[31] &= 127;
-    private_key[31] |= 64;
-
-    // ec scalar for multiplication
-    let scalar = Scalar::from_bytes_mod_order(private_key);
+// A low order public key throws LOW_ORDER_PUBLIC_KEY instead of returning an all zero shared secret
+pub fn diffie_hellman(my_private_key_bytes: &[u8], their_public_key_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let private_key = PrivateKey::from_bytes(my_private_key_bytes)?;
+    let their_public_key = PublicKey::from_bytes(their_public_key_bytes)?;
 
-    // Converts into ec point and performs scalar multiplication
-    let their_public_point = MontgomeryPoint(their_public_key_bytes.try_into().unwrap());
-    let shared_point = scalar * their_public_point;
-
-    // Converts to bytes
-    shared_point.to_bytes().to_vec()
+    Ok(private_key.diffie_hellman(&their_public_key)?.to_vec())
 }
 
 #[wasm_bindgen]
 // This function generates a public key from a seed using the ED25519 algorithm
-pub fn generate_ed25519_public_key(seed: &[u8]) -> Vec<u8> {
+pub fn generate_ed25519_public_key(seed: &[u8]) -> Result<Vec<u8>, JsValue> {
     if seed.len() != 32 {
-        return vec![];
+        return Err(EchoError::InvalidPrivateKey.into());
     }
 
-    // SHA-512 hash of the seed
-    let hash = Sha512::digest(seed);
-
-    let mut scalar_bytes = [0u8; 32];
-    scalar_bytes.copy_from_slice(&hash[..32]);
-
-    // Clamp the scalar
-    scalar_bytes[0] &= 248;
-    scalar_bytes[31] &= 127;
-    scalar_bytes[31] |= 64;
-
-    let scalar = Scalar::from_bytes_mod_order(scalar_bytes);
-
-    let public_point = &scalar * &ED25519_BASEPOINT_POINT;
+    Ok(echo_crypto::keys::ed25519_public_key_from_seed(seed).to_vec())
+}
 
-    public_point.compress().to_bytes().to_vec()
+#[wasm_bindgen]
+// This function generates an Ed25519 seed from the random number generator inside wasm
+pub fn generate_ed25519_seed() -> Result<Vec<u8>, JsValue> {
+    Ok(echo_crypto::keys::generate_ed25519_seed()?.to_vec())
 }
 
 #[wasm_bindgen]
-// This function generates a private key from random bytes in EDWARDS form
-pub fn generate_ed25519_private_key(js_random_bytes: &[u8]) -> Vec<u8> {
+/// This function returns the first 32 random bytes as an Ed25519 seed in EDWARDS form
+/// The seed is handed to JavaScript as plain bytes, this legacy API cannot wipe it
+/// @deprecated Takes randomness from JavaScript and exposes the seed, use generate_ed25519_seed or IdentityKeyPair.generate instead
+pub fn generate_ed25519_private_key(js_random_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
     if js_random_bytes.len() < 32 {
-        return vec![];
+        return Err(EchoError::NotEnoughRandomBytes.into());
     }
 
-    let mut seed = [0u8; 32];
-    seed.copy_from_slice(&js_random_bytes[..32]);
-
-    seed.to_vec()
+    Ok(js_random_bytes[..32].to_vec())
 }
 
 #[wasm_bindgen]
-pub fn derive_x25519_from_ed25519_private(ed25519_seed: &[u8]) -> JsValue {
+pub fn derive_x25519_from_ed25519_private(ed25519_seed: &[u8]) -> Result<JsValue, JsValue> {
     if ed25519_seed.len() != 32 {
-        return JsValue::NULL;
+        return Err(EchoError::InvalidPrivateKey.into());
     }
 
-    // 1. Hash the Ed25519 private seed with SHA-512
-    let hash = Sha512::digest(ed25519_seed);
-
-    // 2. Clamp the first 32 bytes to form X25519 private key
-    let mut x25519_priv_bytes = [0u8; 32];
-    x25519_priv_bytes.copy_from_slice(&hash[..32]);
+    // The X25519 key pair shares its clamped scalar with the Ed25519 seed
+    let key_pair = echo_crypto::keys::x25519_from_ed25519_seed(ed25519_seed);
 
-    x25519_priv_bytes[0] &= 248;
-    x25519_priv_bytes[31] &= 127;
-    x25519_priv_bytes[31] |= 64;
+    // Return both as JS object
+    let result = Object::new();
+    js_sys::Reflect::set(&result, &"x25519_private_key".into(), &Uint8Array::from(&key_pair.private_key.as_bytes()[..]))?;
+    js_sys::Reflect::set(&result, &"x25519_public_key".into(), &Uint8Array::from(&key_pair.public_key.as_bytes()[..]))?;
 
-    // 3. Derive X25519 public key
-    let scalar = Scalar::from_bytes_mod_order(x25519_priv_bytes);
-    let public_point: MontgomeryPoint = scalar * X25519_BASEPOINT;
-    let x25519_pub_bytes = public_point.to_bytes();
+    Ok(result.into())
+}
 
-    // 4. Return both as JS object
-    let result = Object::new();
-    js_sys::Reflect::set(&result, &"x25519_private_key".into(), &Uint8Array::from(&x25519_priv_bytes[..])).unwrap();
-    js_sys::Reflect::set(&result, &"x25519_public_key".into(), &Uint8Array::from(&x25519_pub_bytes[..])).unwrap();
+// This function derives the X25519 public key of a 32 byte private key
+fn x25519_public_key(private_key_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let private_key = PrivateKey::from_bytes(private_key_bytes)?;
+    Ok(private_key.public_key().to_bytes().to_vec())
+}
 
-    result.into()
+// This function builds a clamped X25519 private key from random bytes
+fn x25519_private_key(js_random_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let private_key = PrivateKey::from_random_bytes(js_random_bytes)?;
+    Ok(private_key.to_bytes().to_vec())
 }
 
+#[wasm_bindgen]
+// This function generates a clamped X25519 private key from the random number generator inside wasm
+pub fn generate_x25519_private_key() -> Result<Vec<u8>, JsValue> {
+    let key_pair = KeyPair::generate()?;
+    Ok(key_pair.private_key.to_bytes().to_vec())
+}
 
 #[wasm_bindgen]
 // This function generates a public prekey from a private prekey (Functionally identical to generate_public_key)
-pub fn generate_public_prekey(private_prekey_bytes: &[u8]) -> Vec<u8> {
-    if private_prekey_bytes.len() != 32 {
-        return vec![];
-    }
-
-    let mut private_prekey = [0u8; 32];
-    private_prekey.copy_from_slice(&private_prekey_bytes[..32]);
-
-    // Clamp manually (as per X25519 spec)
-    private_prekey[0] &= 248;
-    private_prekey[31] &= 127;
-    private_prekey[31] |= 64;
-
-    let scalar = Scalar::from_bytes_mod_order(private_prekey);
-    let public_point: MontgomeryPoint = scalar * X25519_BASEPOINT;
-
-    public_point.to_bytes().to_vec()
+pub fn generate_public_prekey(private_prekey_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+    x25519_public_key(private_prekey_bytes)
 }
 
 #[wasm_bindgen]
 // This function generates a private prekey from random bytes (Functionally identical to generate_private_key)
-pub fn generate_private_prekey(js_random_bytes: &[u8]) -> Vec<u8> {
-    let mut private_prekey = [0u8; 32];
-
-    if js_random_bytes.len() < 32 {
-        return vec![];
-    }
-    private_prekey.copy_from_slice(&js_random_bytes[..32]);
-
-    private_prekey[0] &= 248;
-    private_prekey[31] &= 127;
-    private_prekey[31] |= 64;
-
-    private_prekey.to_vec()
+// Legacy: prefer generate_x25519_private_key or SignedPreKey.generate
+pub fn generate_private_prekey(js_random_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+    x25519_private_key(js_random_bytes)
 }
 
 #[wasm_bindgen]
 // This function generates a ephemeral public key
-pub fn generate_public_ephemeral_key(private_prekey_bytes: &[u8]) -> Vec<u8> {
-    if private_prekey_bytes.len() != 32 {
-        return vec![];
-    }
-
-    let mut private_prekey = [0u8; 32];
-    private_prekey.copy_from_slice(&private_prekey_bytes[..32]);
-
-    // Clamp manually (as per X25519 spec)
-    private_prekey[0] &= 248;
-    private_prekey[31] &= 127;
-    private_prekey[31] |= 64;
-
-    let scalar = Scalar::from_bytes_mod_order(private_prekey);
-    let public_point: MontgomeryPoint = scalar * X25519_BASEPOINT;
-
-    public_point.to_bytes().to_vec()
+pub fn generate_public_ephemeral_key(private_prekey_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+    x25519_public_key(private_prekey_bytes)
 }
 
 #[wasm_bindgen]
 // This function generates a private ephemeral key from random bytes
-pub fn generate_private_ephemeral_key(js_random_bytes: &[u8]) -> Vec<u8> {
-    let mut private_prekey = [0u8; 32];
+// Legacy: prefer generate_x25519_private_key or EphemeralKeyPair.generate
+pub fn generate_private_ephemeral_key(js_random_bytes: &[u8]) -> Result<Vec<u8>, JsValue> {
+    x25519_private_key(js_random_bytes)
+}
 
-    if js_random_bytes.len() < 32 {
-        return vec![];
-    }
-    private_prekey.copy_from_slice(&js_random_bytes[..32]);
+#[wasm_bindgen]
+// This function generates a numbered batch of one-time prekeys from random bytes (32 bytes per prekey)
+// Legacy: prefer generate_random_one_time_prekeys
+pub fn generate_one_time_prekeys(start_id: u32, count: u32, js_random_bytes: &[u8]) -> Result<OneTimePreKeyBatch, JsValue> {
+    OneTimePreKeyBatch::generate(start_id, count, js_random_bytes).map_err(JsValue::from)
+}
 
-    private_prekey[0] &= 248;
-    private_prekey[31] &= 127;
-    private_prekey[31] |= 64;
+#[wasm_bindgen]
+// This function generates a numbered batch of one-time prekeys from the random number generator inside wasm
+pub fn generate_random_one_time_prekeys(start_id: u32, count: u32) -> Result<OneTimePreKeyBatch, JsValue> {
+    OneTimePreKeyBatch::generate_random(start_id, count).map_err(JsValue::from)
+}
 
-    private_prekey.to_vec()
+#[wasm_bindgen]
+pub fn hkdf_derive(input_key_material: &[u8], salt: &[u8], info: &[u8], output_len: usize) -> Result<Vec<u8>, JsValue> {
+    Ok(kdf::hkdf_sha256(input_key_material, salt, info, output_len)?.to_vec())
 }
 
 #[wasm_bindgen]
-pub fn hkdf_derive(input_key_material: &[u8], salt: &[u8], info: &[u8], output_len: usize) -> Vec<u8> {
-    let hkdf = Hkdf::<Sha256>::new(Some(salt), input_key_material);
+// This function runs X3DH as the initiator against the peer's prekey bundle
+// The signed prekey signature is verified first, an invalid one throws INVALID_PREKEY_SIGNATURE
+// their_identity comes from TrustStore.ensure_trusted, a bundle with another identity key throws UNTRUSTED_IDENTITY_KEY
+// Returns the shared secret SK and the associated data AD = IK_A || IK_B
+pub fn x3dh_initiate(
+    bundle: &PreKeyBundle,
+    their_identity: &TrustedIdentity,
+    identity_private: &[u8],
+    ephemeral_private: &[u8],
+) -> Result<X3dhResult, JsValue> {
+    x3dh::initiate(bundle, their_identity, identity_private, ephemeral_private).map_err(JsValue::from)
+}
 
-    let mut okm = vec![0u8; output_len];
-    if hkdf.expand(info, &mut okm).is_err() {
-        return vec![]; // Handle failure
-    }
+#[wasm_bindgen]
+// This function runs X3DH as the responder from the initiator's trusted identity key and ephemeral public key
+// The one-time prekey private key must be given whenever the initiator used our one-time prekey
+pub fn x3dh_respond(
+    identity_private: &[u8],
+    signed_prekey_private: &[u8],
+    one_time_prekey_private: Option<Vec<u8>>,
+    their_identity: &TrustedIdentity,
+    their_ephemeral_public: &[u8],
+) -> Result<X3dhResult, JsValue> {
+    x3dh::respond(
+        identity_private,
+        signed_prekey_private,
+        one_time_prekey_private.as_deref(),
+        their_identity,
+        their_ephemeral_public,
+    ).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+// This function runs X3DH as the initiator with typed keys, so an Ed25519 seed or a prekey cannot be passed as the identity key
+pub fn x3dh_initiate_with_keys(
+    bundle: &PreKeyBundle,
+    their_identity: &TrustedIdentity,
+    identity: &IdentityKeyPair,
+    ephemeral: &EphemeralKeyPair,
+) -> Result<X3dhResult, JsValue> {
+    let bundle = bundle.as_core().verify()?;
+    let result = echo_crypto::x3dh::initiate(&bundle, their_identity.as_core(), &identity.as_core().private_key, &ephemeral.as_core().private_key)?;
+    Ok(result.into())
+}
 
-    okm
+#[wasm_bindgen]
+// This function runs X3DH as the responder with typed keys
+// The one-time prekey must be given whenever the initiator used it, it is consumed (take it from OneTimePreKeyBatch.get)
+pub fn x3dh_respond_with_keys(
+    identity: &IdentityKeyPair,
+    signed_prekey: &SignedPreKey,
+    one_time_prekey: Option<OneTimePreKey>,
+    their_identity: &TrustedIdentity,
+    their_ephemeral_key: &X25519PublicKey,
+) -> Result<X3dhResult, JsValue> {
+    Ok(echo_crypto::x3dh::respond(
+        &identity.as_core().private_key,
+        &signed_prekey.as_core().key_pair.private_key,
+        one_time_prekey.as_ref().map(|prekey| &prekey.as_core().private_key),
+        their_identity.as_core(),
+        their_ephemeral_key.as_core(),
+    )?
+    .into())
 }
@@ -0,0 +1,73 @@
+use wasm_bindgen::prelude::*;
+
+pub use echo_crypto::prekeys::MAX_ONE_TIME_PREKEY_BATCH;
+
+// This struct is a single numbered one-time prekey, the private half stays with the owner
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct OneTimePreKey {
+    inner: echo_crypto::OneTimePreKey,
+}
+
+impl OneTimePreKey {
+    pub fn as_core(&self) -> &echo_crypto::OneTimePreKey {
+        &self.inner
+    }
+}
+
+#[wasm_bindgen]
+impl OneTimePreKey {
+    #[wasm_bindgen(getter)]
+    pub fn id(&self) -> u32 {
+        self.inner.id
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn public_key(&self) -> Vec<u8> {
+        self.inner.public_key.to_bytes().to_vec()
+    }
+}
+
+// This struct is a batch of one-time prekeys with consecutive IDs
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct OneTimePreKeyBatch {
+    inner: echo_crypto::OneTimePreKeyBatch,
+}
+
+impl OneTimePreKeyBatch {
+    // This function generates `count` one-time prekeys numbered from `start_id`
+    // 32 random bytes are consumed per prekey
+    pub fn generate(start_id: u32, count: u32, random_bytes: &[u8]) -> Result<OneTimePreKeyBatch, echo_crypto::EchoError> {
+        let inner = echo_crypto::OneTimePreKeyBatch::generate(start_id, count, random_bytes)?;
+        Ok(OneTimePreKeyBatch { inner })
+    }
+
+    // This function generates `count` one-time prekeys numbered from `start_id` from the random number generator inside wasm
+    pub fn generate_random(start_id: u32, count: u32) -> Result<OneTimePreKeyBatch, echo_crypto::EchoError> {
+        let inner = echo_crypto::OneTimePreKeyBatch::generate_with(start_id, count, &mut echo_crypto::OsRng)?;
+        Ok(OneTimePreKeyBatch { inner })
+    }
+
+    pub fn as_core(&self) -> &echo_crypto::OneTimePreKeyBatch {
+        &self.inner
+    }
+}
+
+#[wasm_bindgen]
+impl OneTimePreKeyBatch {
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.inner.prekeys().len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<OneTimePreKey> {
+        self.inner.prekeys().get(index).cloned().map(|inner| OneTimePreKey { inner })
+    }
+
+    // This function encodes the public half of the batch for upload
+    // COUNT (4 bytes BE) || (ID (4 bytes BE) || OPK (32 bytes)) * COUNT
+    pub fn serialize_public(&self) -> Vec<u8> {
+        self.inner.public_bytes()
+    }
+}
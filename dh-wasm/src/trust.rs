@@ -0,0 +1,97 @@
+use wasm_bindgen::prelude::*;
+use crate::keys::X25519PublicKey;
+
+// Result of comparing a fetched identity key with the one remembered for the contact
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrustStatus {
+    Unknown,
+    Trusted,
+    Changed,
+}
+
+impl From<echo_crypto::TrustStatus> for TrustStatus {
+    fn from(status: echo_crypto::TrustStatus) -> TrustStatus {
+        match status {
+            echo_crypto::TrustStatus::Unknown => TrustStatus::Unknown,
+            echo_crypto::TrustStatus::Trusted => TrustStatus::Trusted,
+            echo_crypto::TrustStatus::Changed => TrustStatus::Changed,
+        }
+    }
+}
+
+// This struct is an identity key accepted by a TrustStore, X3DH only runs against one, see echo_crypto::TrustedIdentity
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrustedIdentity {
+    inner: echo_crypto::TrustedIdentity,
+}
+
+impl TrustedIdentity {
+    pub fn as_core(&self) -> &echo_crypto::TrustedIdentity {
+        &self.inner
+    }
+}
+
+#[wasm_bindgen]
+impl TrustedIdentity {
+    #[wasm_bindgen(getter)]
+    pub fn identity_key(&self) -> X25519PublicKey {
+        (*self.inner.identity_key()).into()
+    }
+
+    // Unknown when the contact was seen for the first time, Trusted otherwise
+    #[wasm_bindgen(getter)]
+    pub fn status(&self) -> TrustStatus {
+        self.inner.status().into()
+    }
+}
+
+// This struct remembers the identity key of every contact on first use, see echo_crypto::TrustStore
+// Call ensure_trusted with the identity key fetched from the server before every session start
+#[wasm_bindgen]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrustStore {
+    inner: echo_crypto::TrustStore,
+}
+
+impl TrustStore {
+    pub fn as_core(&self) -> &echo_crypto::TrustStore {
+        &self.inner
+    }
+}
+
+impl From<echo_crypto::TrustStore> for TrustStore {
+    fn from(inner: echo_crypto::TrustStore) -> TrustStore {
+        TrustStore { inner }
+    }
+}
+
+#[wasm_bindgen]
+impl TrustStore {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> TrustStore {
+        TrustStore::default()
+    }
+
+    // This function reports whether the key is the remembered one without changing the store
+    pub fn check(&self, contact_id: &str, identity_key: &X25519PublicKey) -> TrustStatus {
+        self.inner.check(contact_id, identity_key.as_core()).into()
+    }
+
+    // This function accepts an unknown or trusted key and throws IDENTITY_KEY_CHANGED for a changed one
+    // The returned TrustedIdentity is what the X3DH functions take as the contact's identity key
+    pub fn ensure_trusted(&mut self, contact_id: &str, identity_key: &X25519PublicKey) -> Result<TrustedIdentity, JsValue> {
+        let inner = self.inner.ensure_trusted(contact_id, identity_key.as_core())?;
+        Ok(TrustedIdentity { inner })
+    }
+
+    // This function must only be called after the user explicitly accepted the new key
+    pub fn approve(&mut self, contact_id: &str, identity_key: &X25519PublicKey) -> TrustedIdentity {
+        TrustedIdentity { inner: self.inner.approve(contact_id, identity_key.as_core()) }
+    }
+
+    pub fn forget(&mut self, contact_id: &str) -> bool {
+        self.inner.forget(contact_id)
+    }
+}
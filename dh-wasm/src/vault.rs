@@ -0,0 +1,76 @@
+use wasm_bindgen::prelude::*;
+use echo_crypto::Argon2Params;
+use crate::keystore::KeyStore;
+use crate::trust::TrustStore;
+use ratchet_wasm::SessionRecord;
+
+// This struct is an unlocked password protected vault holding the key store and the serialized ratchet sessions
+// Its bytes (see to_bytes) are the only form that should reach localStorage, see echo_crypto::vault for the layout
+#[wasm_bindgen]
+pub struct Vault {
+    inner: echo_crypto::Vault,
+}
+
+#[wasm_bindgen]
+impl Vault {
+    // This function creates a vault for the key store, the key is derived from the password with Argon2id
+    pub fn create(password: &str, key_store: &KeyStore) -> Result<Vault, JsValue> {
+        let inner = echo_crypto::Vault::create(password.as_bytes(), Argon2Params::default(), key_store.as_core().clone())?;
+        Ok(Vault { inner })
+    }
+
+    // This function decrypts stored vault bytes, a wrong password throws an EchoError with code WRONG_PASSWORD
+    pub fn unlock(bytes: &[u8], password: &str) -> Result<Vault, JsValue> {
+        let inner = echo_crypto::Vault::unlock(bytes, password.as_bytes())?;
+        Ok(Vault { inner })
+    }
+
+    // This function encrypts the current contents for storage
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsValue> {
+        Ok(self.inner.to_bytes()?)
+    }
+
+    // This function re-wraps the data key under the new password, the contents are not re-encrypted
+    pub fn change_password(&mut self, current_password: &str, new_password: &str) -> Result<(), JsValue> {
+        self.inner
+            .change_password(current_password.as_bytes(), new_password.as_bytes(), Argon2Params::default())
+            .map_err(JsValue::from)
+    }
+
+    // This function returns a copy of the stored key store, call set_key_store after changing it
+    #[wasm_bindgen(getter)]
+    pub fn key_store(&self) -> KeyStore {
+        self.inner.key_store.clone().into()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_key_store(&mut self, key_store: &KeyStore) {
+        self.inner.key_store = key_store.as_core().clone();
+    }
+
+    // This function returns a copy of the contacts' remembered identity keys, call set_trust_store after changing it
+    #[wasm_bindgen(getter)]
+    pub fn trust_store(&self) -> TrustStore {
+        self.inner.trust_store.clone().into()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_trust_store(&mut self, trust_store: &TrustStore) {
+        self.inner.trust_store = trust_store.as_core().clone();
+    }
+
+    // This function returns the record stored for a conversation, it is decoded inside wasm
+    pub fn session(&self, id: &str) -> Result<Option<SessionRecord>, JsValue> {
+        Ok(self.inner.session_record(id)?.map(SessionRecord::from))
+    }
+
+    // This function stores the current state of a conversation, call it again after every encrypt or decrypt
+    // The record is serialized inside wasm, the ratchet keys never reach JavaScript
+    pub fn set_session(&mut self, id: &str, record: &SessionRecord) {
+        self.inner.set_session_record(id, record.as_core());
+    }
+
+    pub fn remove_session(&mut self, id: &str) -> bool {
+        self.inner.remove_session(id)
+    }
+}
@@ -0,0 +1,79 @@
+use wasm_bindgen::prelude::*;
+use echo_crypto::{EchoError, PrivateKey, PublicKey};
+use crate::bundle::PreKeyBundle;
+use crate::trust::TrustedIdentity;
+
+// This struct is the outcome of X3DH: the shared secret SK and the associated data AD = IK_A || IK_B
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct X3dhResult {
+    inner: echo_crypto::X3dhResult,
+}
+
+impl X3dhResult {
+    pub fn shared_secret_bytes(&self) -> &[u8; 32] {
+        &self.inner.shared_secret
+    }
+
+    pub fn associated_data_bytes(&self) -> &[u8; 64] {
+        &self.inner.associated_data
+    }
+}
+
+impl From<echo_crypto::X3dhResult> for X3dhResult {
+    fn from(inner: echo_crypto::X3dhResult) -> X3dhResult {
+        X3dhResult { inner }
+    }
+}
+
+#[wasm_bindgen]
+impl X3dhResult {
+    #[wasm_bindgen(getter)]
+    pub fn shared_secret(&self) -> Vec<u8> {
+        self.inner.shared_secret.to_vec()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn associated_data(&self) -> Vec<u8> {
+        self.inner.associated_data.to_vec()
+    }
+}
+
+// Initiator side (Alice), see echo_crypto::x3dh::initiate
+// Fails with InvalidPreKeySignature when the signed prekey is not signed by the bundle's identity key
+// and with UntrustedIdentityKey when the bundle's identity key is not the trusted one
+pub fn initiate(
+    bundle: &PreKeyBundle,
+    their_identity: &TrustedIdentity,
+    identity_private: &[u8],
+    ephemeral_private: &[u8],
+) -> Result<X3dhResult, EchoError> {
+    let identity_private = PrivateKey::from_bytes(identity_private)?;
+    let ephemeral_private = PrivateKey::from_bytes(ephemeral_private)?;
+
+    let inner = echo_crypto::x3dh::initiate(&bundle.as_core().verify()?, their_identity.as_core(), &identity_private, &ephemeral_private)?;
+    Ok(X3dhResult { inner })
+}
+
+// Responder side (Bob), see echo_crypto::x3dh::respond
+pub fn respond(
+    identity_private: &[u8],
+    signed_prekey_private: &[u8],
+    one_time_prekey_private: Option<&[u8]>,
+    their_identity: &TrustedIdentity,
+    their_ephemeral_public: &[u8],
+) -> Result<X3dhResult, EchoError> {
+    let identity_private = PrivateKey::from_bytes(identity_private)?;
+    let signed_prekey_private = PrivateKey::from_bytes(signed_prekey_private)?;
+    let one_time_prekey_private = one_time_prekey_private.map(PrivateKey::from_bytes).transpose()?;
+    let their_ephemeral_public = PublicKey::from_bytes(their_ephemeral_public)?;
+
+    let inner = echo_crypto::x3dh::respond(
+        &identity_private,
+        &signed_prekey_private,
+        one_time_prekey_private.as_ref(),
+        their_identity.as_core(),
+        &their_ephemeral_public,
+    )?;
+    Ok(X3dhResult { inner })
+}
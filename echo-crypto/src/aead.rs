@@ -0,0 +1,124 @@
+use aes_gcm::aead::{Aead, NewAead, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use zeroize::Zeroizing;
+
+use crate::error::EchoError;
+use crate::rng::{self, CryptoRng, OsRng, RngCore};
+
+pub const KEY_LENGTH: usize = 32;
+pub const NONCE_LENGTH: usize = 12;
+pub const TAG_LENGTH: usize = 16;
+
+// Envelope layout produced by seal_envelope: VERSION (1 byte) || NONCE (12 bytes) || CIPHERTEXT + TAG
+pub const ENVELOPE_VERSION: u8 = 1;
+const ENVELOPE_HEADER_LENGTH: usize = 1 + NONCE_LENGTH;
+
+// Message header layout used as associated data:
+// VERSION (1 byte) || SENDER_LEN (2 bytes BE) || SENDER || RECIPIENT_LEN (2 bytes BE) || RECIPIENT
+// || MESSAGE_NUMBER (4 bytes BE) || EPHEMERAL_KEY (32 bytes)
+pub const MESSAGE_HEADER_VERSION: u8 = 1;
+
+// This function performs AES-256-GCM encryption over bytes, every other encryption function is built on it
+pub fn aes_gcm_encrypt(plaintext: &[u8], key: &[u8], nonce: &[u8], aad: &[u8]) -> Result<Vec<u8>, EchoError> {
+    if key.len() != KEY_LENGTH {
+        return Err(EchoError::InvalidSymmetricKey);
+    }
+
+    if nonce.len() != NONCE_LENGTH {
+        return Err(EchoError::InvalidNonce);
+    }
+
+    let key = Key::from_slice(key);
+    let cipher = Aes256Gcm::new(key);
+
+    let nonce = Nonce::from_slice(nonce);
+    cipher.encrypt(nonce, Payload { msg: plaintext, aad })
+        .map_err(|_| EchoError::EncryptionFailed)
+}
+
+// This function performs AES-256-GCM decryption over bytes, every other decryption function is built on it
+pub fn aes_gcm_decrypt(ciphertext: &[u8], key: &[u8], nonce: &[u8], aad: &[u8]) -> Result<Vec<u8>, EchoError> {
+    if key.len() != KEY_LENGTH {
+        return Err(EchoError::InvalidSymmetricKey);
+    }
+
+    if nonce.len() != NONCE_LENGTH {
+        return Err(EchoError::InvalidNonce);
+    }
+
+    let key = Key::from_slice(key);
+    let cipher = Aes256Gcm::new(key);
+
+    let nonce = Nonce::from_slice(nonce);
+    cipher.decrypt(nonce, Payload { msg: ciphertext, aad })
+        .map_err(|_| EchoError::DecryptionFailed)
+}
+
+// This function generates a random 256-bit AES-GCM key
+pub fn generate_key() -> Result<Zeroizing<[u8; KEY_LENGTH]>, EchoError> {
+    generate_key_with(&mut OsRng)
+}
+
+pub fn generate_key_with<R: RngCore + CryptoRng + ?Sized>(rng: &mut R) -> Result<Zeroizing<[u8; KEY_LENGTH]>, EchoError> {
+    let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+    rng::fill(rng, key.as_mut())?;
+    Ok(key)
+}
+
+// This function encrypts the plaintext under a fresh random nonce and returns the envelope bytes
+pub fn seal_envelope(plaintext: &[u8], key: &[u8], aad: &[u8]) -> Result<Vec<u8>, EchoError> {
+    // A new 96-bit nonce is drawn for every message, it is never supplied by the caller
+    let mut nonce_bytes = [0u8; NONCE_LENGTH];
+    rng::fill(&mut OsRng, &mut nonce_bytes)?;
+
+    let ciphertext = aes_gcm_encrypt(plaintext, key, &nonce_bytes, aad)?;
+
+    let mut envelope = Vec::with_capacity(ENVELOPE_HEADER_LENGTH + ciphertext.len());
+    envelope.push(ENVELOPE_VERSION);
+    envelope.extend_from_slice(&nonce_bytes);
+    envelope.extend_from_slice(&ciphertext);
+
+    Ok(envelope)
+}
+
+// This function parses the envelope produced by seal_envelope and decrypts it with the embedded nonce
+pub fn open_envelope(envelope: &[u8], key: &[u8], aad: &[u8]) -> Result<Vec<u8>, EchoError> {
+    if key.len() != KEY_LENGTH {
+        return Err(EchoError::InvalidSymmetricKey);
+    }
+
+    // The smallest valid envelope is the header plus the tag of an empty plaintext
+    if envelope.len() < ENVELOPE_HEADER_LENGTH + TAG_LENGTH {
+        return Err(EchoError::MalformedEnvelope);
+    }
+
+    if envelope[0] != ENVELOPE_VERSION {
+        return Err(EchoError::UnsupportedVersion);
+    }
+
+    let nonce = &envelope[1..ENVELOPE_HEADER_LENGTH];
+    aes_gcm_decrypt(&envelope[ENVELOPE_HEADER_LENGTH..], key, nonce, aad)
+}
+
+// This function builds the canonical encoding of a message header
+// Every variable length field is length prefixed so two different headers never encode to the same bytes
+pub fn message_header_bytes(sender_id: &str, recipient_id: &str, message_number: u32, ephemeral_key: &[u8]) -> Result<Vec<u8>, EchoError> {
+    if ephemeral_key.len() != 32 {
+        return Err(EchoError::InvalidPublicKey);
+    }
+
+    if sender_id.len() > u16::MAX as usize || recipient_id.len() > u16::MAX as usize {
+        return Err(EchoError::MalformedMessageHeader);
+    }
+
+    let mut header = Vec::with_capacity(1 + 2 + sender_id.len() + 2 + recipient_id.len() + 4 + 32);
+    header.push(MESSAGE_HEADER_VERSION);
+    header.extend_from_slice(&(sender_id.len() as u16).to_be_bytes());
+    header.extend_from_slice(sender_id.as_bytes());
+    header.extend_from_slice(&(recipient_id.len() as u16).to_be_bytes());
+    header.extend_from_slice(recipient_id.as_bytes());
+    header.extend_from_slice(&message_number.to_be_bytes());
+    header.extend_from_slice(ephemeral_key);
+
+    Ok(header)
+}
@@ -0,0 +1,130 @@
+use crate::error::EchoError;
+use crate::keys::PublicKey;
+use crate::prekeys::{OneTimePreKey, SignedPreKey};
+use crate::xeddsa;
+
+// Bundle serialization layout (all integers big endian):
+// VERSION (1 byte) || IK (32 bytes) || SPK_ID (4 bytes) || SPK (32 bytes) || SPK_SIGNATURE (64 bytes)
+// || HAS_OPK (1 byte, 0 or 1) [|| OPK_ID (4 bytes) || OPK (32 bytes)]
+pub const BUNDLE_VERSION: u8 = 1;
+const BUNDLE_LENGTH_WITHOUT_OPK: usize = 1 + 32 + 4 + 32 + 64 + 1;
+const BUNDLE_LENGTH_WITH_OPK: usize = BUNDLE_LENGTH_WITHOUT_OPK + 4 + 32;
+
+// This struct holds the public keys a user publishes so others can start a session with them
+// IK is the identity key, SPK the signed prekey with its XEdDSA signature and OPK an optional one-time prekey
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreKeyBundle {
+    pub identity_key: PublicKey,
+    pub signed_prekey_id: u32,
+    pub signed_prekey: PublicKey,
+    pub signed_prekey_signature: [u8; 64],
+    pub one_time_prekey: Option<(u32, PublicKey)>,
+}
+
+impl PreKeyBundle {
+    // This function builds the bundle published by the owner of the signed prekey and one-time prekey
+    pub fn new(identity_key: PublicKey, signed_prekey: &SignedPreKey, one_time_prekey: Option<&OneTimePreKey>) -> PreKeyBundle {
+        PreKeyBundle {
+            identity_key,
+            signed_prekey_id: signed_prekey.id,
+            signed_prekey: signed_prekey.key_pair.public_key,
+            signed_prekey_signature: signed_prekey.signature,
+            one_time_prekey: one_time_prekey.map(|prekey| (prekey.id, prekey.public_key)),
+        }
+    }
+
+    // This function builds a bundle from raw X25519 public keys and the signature over the signed prekey
+    pub fn from_keys(
+        identity_key: &[u8],
+        signed_prekey_id: u32,
+        signed_prekey: &[u8],
+        signed_prekey_signature: &[u8],
+        one_time_prekey: Option<(u32, &[u8])>,
+    ) -> Result<PreKeyBundle, EchoError> {
+        let identity_key = PublicKey::from_bytes(identity_key).map_err(|_| EchoError::InvalidPublicKey)?;
+        let signed_prekey = PublicKey::from_bytes(signed_prekey).map_err(|_| EchoError::InvalidPublicKey)?;
+        let signed_prekey_signature = signed_prekey_signature.try_into().map_err(|_| EchoError::InvalidSignature)?;
+        let one_time_prekey = match one_time_prekey {
+            Some((id, key)) => Some((id, PublicKey::from_bytes(key).map_err(|_| EchoError::InvalidPublicKey)?)),
+            None => None,
+        };
+
+        Ok(PreKeyBundle {
+            identity_key,
+            signed_prekey_id,
+            signed_prekey,
+            signed_prekey_signature,
+            one_time_prekey,
+        })
+    }
+
+    // This function encodes the bundle into its stable binary form
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(BUNDLE_LENGTH_WITH_OPK);
+        bytes.push(BUNDLE_VERSION);
+        bytes.extend_from_slice(self.identity_key.as_bytes());
+        bytes.extend_from_slice(&self.signed_prekey_id.to_be_bytes());
+        bytes.extend_from_slice(self.signed_prekey.as_bytes());
+        bytes.extend_from_slice(&self.signed_prekey_signature);
+
+        match &self.one_time_prekey {
+            Some((id, key)) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&id.to_be_bytes());
+                bytes.extend_from_slice(key.as_bytes());
+            }
+            None => bytes.push(0),
+        }
+
+        bytes
+    }
+
+    // This function parses and validates a bundle returned by the server
+    // Any unknown version, bad flag or trailing data is rejected
+    pub fn from_bytes(bytes: &[u8]) -> Result<PreKeyBundle, EchoError> {
+        if bytes.len() < BUNDLE_LENGTH_WITHOUT_OPK {
+            return Err(EchoError::MalformedBundle);
+        }
+
+        if bytes[0] != BUNDLE_VERSION {
+            return Err(EchoError::UnsupportedVersion);
+        }
+
+        let identity_key = &bytes[1..33];
+        let signed_prekey_id = u32::from_be_bytes(bytes[33..37].try_into().unwrap());
+        let signed_prekey = &bytes[37..69];
+        let signed_prekey_signature = &bytes[69..133];
+
+        let one_time_prekey = match bytes[133] {
+            0 if bytes.len() == BUNDLE_LENGTH_WITHOUT_OPK => None,
+            1 if bytes.len() == BUNDLE_LENGTH_WITH_OPK => {
+                let id = u32::from_be_bytes(bytes[134..138].try_into().unwrap());
+                Some((id, &bytes[138..170]))
+            }
+            _ => return Err(EchoError::MalformedBundle),
+        };
+
+        PreKeyBundle::from_keys(identity_key, signed_prekey_id, signed_prekey, signed_prekey_signature, one_time_prekey)
+    }
+
+    // This function checks the signed prekey signature against the bundle's identity key, see xeddsa::verify_strict
+    // X3DH only accepts the returned VerifiedPreKeyBundle, so this check cannot be skipped
+    pub fn verify(&self) -> Result<VerifiedPreKeyBundle, EchoError> {
+        if !xeddsa::verify_strict(&self.identity_key, self.signed_prekey.as_bytes(), &self.signed_prekey_signature) {
+            return Err(EchoError::InvalidPreKeySignature);
+        }
+
+        Ok(VerifiedPreKeyBundle(self.clone()))
+    }
+}
+
+// This struct is a bundle whose signed prekey is known to be signed by its identity key
+// It can only be built by PreKeyBundle::verify
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifiedPreKeyBundle(PreKeyBundle);
+
+impl VerifiedPreKeyBundle {
+    pub fn bundle(&self) -> &PreKeyBundle {
+        &self.0
+    }
+}
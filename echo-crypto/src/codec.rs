@@ -0,0 +1,51 @@
+use crate::error::EchoError;
+use crate::keys::{KeyPair, PrivateKey};
+
+// Cursor over a serialized structure, every read past the end and any trailing data fail with `error`
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    error: EchoError,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8], error: EchoError) -> Reader<'a> {
+        Reader { bytes, error }
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], EchoError> {
+        if self.bytes.len() < len {
+            return Err(self.error);
+        }
+
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N], EchoError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, EchoError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, EchoError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    pub(crate) fn key_pair(&mut self) -> Result<KeyPair, EchoError> {
+        let private_key = PrivateKey::from_bytes(self.take(32)?)?;
+        let public_key = private_key.public_key();
+        Ok(KeyPair { private_key, public_key })
+    }
+
+    // This function returns everything that has not been read yet
+    pub(crate) fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.bytes)
+    }
+
+    pub(crate) fn finish(self) -> Result<(), EchoError> {
+        if self.bytes.is_empty() { Ok(()) } else { Err(self.error) }
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt;
+
+// Every failure of the Echo crypto code, each variant has a stable code the UI can match on
+// Codes are part of the public API: never rename or reuse one, add a new variant instead
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EchoError {
+    InvalidPrivateKey,
+    InvalidPublicKey,
+    InvalidSymmetricKey,
+    InvalidNonce,
+    InvalidSignature,
+    InvalidOutputLength,
+    NotEnoughRandomBytes,
+    RandomnessUnavailable,
+    EncryptionFailed,
+    DecryptionFailed,
+    InvalidEncoding,
+    MalformedEnvelope,
+    MalformedBundle,
+    MalformedMessageHeader,
+    UnsupportedVersion,
+    InvalidPreKeyBatch,
+    SessionNotReady,
+    DuplicateMessage,
+    TooManySkippedMessages,
+    InvalidScalar,
+    InvalidPoint,
+    KeyNotFound,
+    KeyStoreFull,
+    MalformedKeyStore,
+    WrongPassword,
+    InvalidKdfParams,
+    MalformedVault,
+    MalformedSessionRecord,
+    MalformedFingerprint,
+    IdentityKeyChanged,
+    MalformedTrustStore,
+    UntrustedIdentityKey,
+    InvalidPreKeySignature,
+    LowOrderPublicKey,
+}
+
+impl EchoError {
+    // This function returns the stable machine readable code of the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            EchoError::InvalidPrivateKey => "INVALID_PRIVATE_KEY",
+            EchoError::InvalidPublicKey => "INVALID_PUBLIC_KEY",
+            EchoError::InvalidSymmetricKey => "INVALID_SYMMETRIC_KEY",
+            EchoError::InvalidNonce => "INVALID_NONCE",
+            EchoError::InvalidSignature => "INVALID_SIGNATURE",
+            EchoError::InvalidOutputLength => "INVALID_OUTPUT_LENGTH",
+            EchoError::NotEnoughRandomBytes => "NOT_ENOUGH_RANDOM_BYTES",
+            EchoError::RandomnessUnavailable => "RANDOMNESS_UNAVAILABLE",
+            EchoError::EncryptionFailed => "ENCRYPTION_FAILED",
+            EchoError::DecryptionFailed => "DECRYPTION_FAILED",
+            EchoError::InvalidEncoding => "INVALID_ENCODING",
+            EchoError::MalformedEnvelope => "MALFORMED_ENVELOPE",
+            EchoError::MalformedBundle => "MALFORMED_BUNDLE",
+            EchoError::MalformedMessageHeader => "MALFORMED_MESSAGE_HEADER",
+            EchoError::UnsupportedVersion => "UNSUPPORTED_VERSION",
+            EchoError::InvalidPreKeyBatch => "INVALID_PREKEY_BATCH",
+            EchoError::SessionNotReady => "SESSION_NOT_READY",
+            EchoError::DuplicateMessage => "DUPLICATE_MESSAGE",
+            EchoError::TooManySkippedMessages => "TOO_MANY_SKIPPED_MESSAGES",
+            EchoError::InvalidScalar => "INVALID_SCALAR",
+            EchoError::InvalidPoint => "INVALID_POINT",
+            EchoError::KeyNotFound => "KEY_NOT_FOUND",
+            EchoError::KeyStoreFull => "KEY_STORE_FULL",
+            EchoError::MalformedKeyStore => "MALFORMED_KEY_STORE",
+            EchoError::WrongPassword => "WRONG_PASSWORD",
+            EchoError::InvalidKdfParams => "INVALID_KDF_PARAMS",
+            EchoError::MalformedVault => "MALFORMED_VAULT",
+            EchoError::MalformedSessionRecord => "MALFORMED_SESSION_RECORD",
+            EchoError::MalformedFingerprint => "MALFORMED_FINGERPRINT",
+            EchoError::IdentityKeyChanged => "IDENTITY_KEY_CHANGED",
+            EchoError::MalformedTrustStore => "MALFORMED_TRUST_STORE",
+            EchoError::UntrustedIdentityKey => "UNTRUSTED_IDENTITY_KEY",
+            EchoError::InvalidPreKeySignature => "INVALID_PREKEY_SIGNATURE",
+            EchoError::LowOrderPublicKey => "LOW_ORDER_PUBLIC_KEY",
+        }
+    }
+
+    // This function returns a human readable description of the error
+    pub fn message(&self) -> &'static str {
+        match self {
+            EchoError::InvalidPrivateKey => "Private key must be 32 bytes",
+            EchoError::InvalidPublicKey => "Public key must be 32 bytes",
+            EchoError::InvalidSymmetricKey => "Symmetric key must be 32 bytes",
+            EchoError::InvalidNonce => "Nonce must be 12 bytes",
+            EchoError::InvalidSignature => "Signature is malformed",
+            EchoError::InvalidOutputLength => "Requested output length is not supported",
+            EchoError::NotEnoughRandomBytes => "Not enough random bytes",
+            EchoError::RandomnessUnavailable => "Random number generator is unavailable",
+            EchoError::EncryptionFailed => "Encryption failed",
+            EchoError::DecryptionFailed => "Decryption failed",
+            EchoError::InvalidEncoding => "Input is not valid hex or UTF-8",
+            EchoError::MalformedEnvelope => "Envelope is malformed",
+            EchoError::MalformedBundle => "Prekey bundle is malformed",
+            EchoError::MalformedMessageHeader => "Message header is malformed",
+            EchoError::UnsupportedVersion => "Unsupported format version",
+            EchoError::InvalidPreKeyBatch => "Invalid one-time prekey batch",
+            EchoError::SessionNotReady => "Session cannot send or receive yet",
+            EchoError::DuplicateMessage => "Duplicate or expired message",
+            EchoError::TooManySkippedMessages => "Too many skipped messages",
+            EchoError::InvalidScalar => "Scalar must be 32 bytes",
+            EchoError::InvalidPoint => "Curve point must be 32 bytes",
+            EchoError::KeyNotFound => "No such key in the key store",
+            EchoError::KeyStoreFull => "Key store has run out of handles",
+            EchoError::MalformedKeyStore => "Key store is malformed",
+            EchoError::WrongPassword => "Wrong password",
+            EchoError::InvalidKdfParams => "Unsupported Argon2 parameters",
+            EchoError::MalformedVault => "Vault is malformed",
+            EchoError::MalformedSessionRecord => "Session record is malformed",
+            EchoError::MalformedFingerprint => "Scanned fingerprint is malformed",
+            EchoError::IdentityKeyChanged => "Contact identity key has changed and must be approved",
+            EchoError::MalformedTrustStore => "Trust store is malformed",
+            EchoError::UntrustedIdentityKey => "Identity key was not accepted by the trust store",
+            EchoError::InvalidPreKeySignature => "Signed prekey is not signed by the identity key",
+            EchoError::LowOrderPublicKey => "Public key is of low order, the shared secret would be all zero",
+        }
+    }
+}
+
+impl fmt::Display for EchoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for EchoError {}
+
+// With the wasm feature errors cross into JavaScript as Error objects with name "EchoError" and a `code` property
+#[cfg(feature = "wasm")]
+impl From<EchoError> for wasm_bindgen::JsValue {
+    fn from(error: EchoError) -> wasm_bindgen::JsValue {
+        let js_error = js_sys::Error::new(error.message());
+        js_error.set_name("EchoError");
+
+        // Reflect::set only fails on frozen or non-object targets, a fresh Error is neither
+        let _ = js_sys::Reflect::set(&js_error, &"code".into(), &error.code().into());
+
+        js_error.into()
+    }
+}
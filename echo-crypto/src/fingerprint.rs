@@ -0,0 +1,100 @@
+use crate::error::EchoError;
+use crate::keys::PublicKey;
+use sha2::{Digest, Sha512};
+
+// Version mixed into the iterated hash, changing it changes every safety number
+pub const FINGERPRINT_VERSION: u16 = 0;
+
+// Number of SHA-512 iterations per side, as used by Signal
+pub const FINGERPRINT_ITERATIONS: u32 = 5200;
+
+// Scannable payload layout: VERSION (1 byte) || LOCAL_FINGERPRINT (32 bytes) || REMOTE_FINGERPRINT (32 bytes)
+pub const SCANNABLE_VERSION: u8 = 1;
+const SCANNABLE_LENGTH: usize = 1 + 32 + 32;
+
+// This function computes the fingerprint of one user
+// H_0 = SHA-512(VERSION (2 bytes BE) || IK || STABLE_ID), H_i = SHA-512(H_(i-1) || IK), the result is the first 32 bytes of the last hash
+fn user_fingerprint(stable_id: &[u8], identity_key: &PublicKey) -> [u8; 32] {
+    let mut hash = Sha512::new()
+        .chain_update(FINGERPRINT_VERSION.to_be_bytes())
+        .chain_update(identity_key.as_bytes())
+        .chain_update(stable_id)
+        .finalize();
+
+    for _ in 0..FINGERPRINT_ITERATIONS {
+        hash = Sha512::new().chain_update(hash).chain_update(identity_key.as_bytes()).finalize();
+    }
+
+    hash[..32].try_into().unwrap()
+}
+
+// This function turns the first 30 bytes of a fingerprint into 30 digits, six 5 byte chunks each taken mod 100000
+fn displayable(fingerprint: &[u8; 32]) -> String {
+    fingerprint[..30]
+        .chunks_exact(5)
+        .map(|chunk| {
+            let value = chunk.iter().fold(0u64, |acc, &byte| (acc << 8) | u64::from(byte));
+            format!("{:05}", value % 100_000)
+        })
+        .collect()
+}
+
+// This struct is the safety number of a conversation as seen by the local user
+// Both users get the same 60 digit number, the scannable payload has the two halves in opposite order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fingerprint {
+    local: [u8; 32],
+    remote: [u8; 32],
+}
+
+impl Fingerprint {
+    // This function computes the fingerprint for a local and a remote user
+    // The stable IDs must be identifiers that never change for a user (e.g. the account ID, not a display name)
+    pub fn new(local_id: &[u8], local_identity_key: &PublicKey, remote_id: &[u8], remote_identity_key: &PublicKey) -> Fingerprint {
+        Fingerprint {
+            local: user_fingerprint(local_id, local_identity_key),
+            remote: user_fingerprint(remote_id, remote_identity_key),
+        }
+    }
+
+    // This function returns the 60 digit safety number, the smaller half comes first so both users see the same digits
+    pub fn safety_number(&self) -> String {
+        let local = displayable(&self.local);
+        let remote = displayable(&self.remote);
+
+        if local <= remote { local + &remote } else { remote + &local }
+    }
+
+    // This function encodes the payload to show as a QR code
+    pub fn scannable(&self) -> [u8; SCANNABLE_LENGTH] {
+        let mut bytes = [0u8; SCANNABLE_LENGTH];
+        bytes[0] = SCANNABLE_VERSION;
+        bytes[1..33].copy_from_slice(&self.local);
+        bytes[33..].copy_from_slice(&self.remote);
+        bytes
+    }
+
+    // This function checks a payload scanned from the other user's screen
+    // Their local half must be our remote half and the other way round
+    pub fn matches_scanned(&self, scanned: &[u8]) -> Result<bool, EchoError> {
+        let (their_local, their_remote) = decode_scannable(scanned)?;
+        Ok(their_local == self.remote && their_remote == self.local)
+    }
+}
+
+// This function parses a scannable payload into its (local, remote) halves
+fn decode_scannable(bytes: &[u8]) -> Result<([u8; 32], [u8; 32]), EchoError> {
+    if bytes.is_empty() {
+        return Err(EchoError::MalformedFingerprint);
+    }
+
+    if bytes[0] != SCANNABLE_VERSION {
+        return Err(EchoError::UnsupportedVersion);
+    }
+
+    if bytes.len() != SCANNABLE_LENGTH {
+        return Err(EchoError::MalformedFingerprint);
+    }
+
+    Ok((bytes[1..33].try_into().unwrap(), bytes[33..].try_into().unwrap()))
+}
@@ -0,0 +1,53 @@
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+
+use zeroize::Zeroizing;
+
+use crate::error::EchoError;
+
+// This function derives output_len bytes with HKDF-SHA256, the output is wiped when dropped
+pub fn hkdf_sha256(input_key_material: &[u8], salt: &[u8], info: &[u8], output_len: usize) -> Result<Zeroizing<Vec<u8>>, EchoError> {
+    let hkdf = Hkdf::<Sha256>::new(Some(salt), input_key_material);
+
+    let mut okm = Zeroizing::new(vec![0u8; output_len]);
+    hkdf.expand(info, &mut okm).map_err(|_| EchoError::InvalidOutputLength)?;
+
+    Ok(okm)
+}
+
+// This function derives a 32 byte key with HKDF-SHA256, used where the output length is fixed
+pub fn hkdf_sha256_32(input_key_material: &[u8], salt: &[u8], info: &[u8]) -> Zeroizing<[u8; 32]> {
+    let hkdf = Hkdf::<Sha256>::new(Some(salt), input_key_material);
+
+    let mut okm = Zeroizing::new([0u8; 32]);
+    hkdf.expand(info, okm.as_mut()).expect("32 bytes is a valid HKDF-SHA256 output length");
+
+    okm
+}
+
+// This function derives a symmetric key from the shared secret using HKDF
+pub fn derive_symmetric_key(shared_secret: &[u8; 32]) -> Zeroizing<[u8; 32]> {
+    let hk = Hkdf::<Sha256>::new(None, shared_secret);
+    let mut okm = Zeroizing::new([0u8; 32]);
+    hk.expand(b"message-encryption", okm.as_mut()).unwrap();
+
+    okm
+}
+
+// This function computes HMAC-SHA256 of the data under the key
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Zeroizing<[u8; 32]> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    Zeroizing::new(mac.finalize().into_bytes().into())
+}
+
+// This function computes the SHA-512 hash of the input data and returns a 64-byte array
+pub fn sha512_bytes(data: &[u8]) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    hasher.update(data);
+    let result = hasher.finalize();
+    let mut out = [0u8; 64];
+    out.copy_from_slice(&result);
+    out
+}
@@ -0,0 +1,166 @@
+use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::Scalar;
+use sha2::{Digest, Sha512};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::error::EchoError;
+use crate::rng::{self, CryptoRng, OsRng, RngCore};
+
+// Clamp the byte array according to X25519 rules
+pub fn clamp(private_key: &mut [u8; 32]) {
+    private_key[0] &= 248;
+    private_key[31] &= 127;
+    private_key[31] |= 64;
+}
+
+// X25519 private key, always stored clamped and wiped from memory when dropped
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct PrivateKey([u8; 32]);
+
+// X25519 public key (Montgomery u-coordinate)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PublicKey([u8; 32]);
+
+// X25519 key pair
+#[derive(Clone)]
+pub struct KeyPair {
+    pub private_key: PrivateKey,
+    pub public_key: PublicKey,
+}
+
+impl PrivateKey {
+    // This function builds a private key from exactly 32 bytes, clamping them
+    pub fn from_bytes(bytes: &[u8]) -> Result<PrivateKey, EchoError> {
+        let bytes: &[u8; 32] = bytes.try_into().map_err(|_| EchoError::InvalidPrivateKey)?;
+        Ok(PrivateKey::from_array(*bytes))
+    }
+
+    // This function takes ownership of the 32 bytes so no unwiped copy is left behind
+    fn from_array(bytes: [u8; 32]) -> PrivateKey {
+        let mut private_key = PrivateKey(bytes);
+        clamp(&mut private_key.0);
+        private_key
+    }
+
+    // This function builds a private key from the first 32 bytes of the random input
+    pub fn from_random_bytes(random_bytes: &[u8]) -> Result<PrivateKey, EchoError> {
+        if random_bytes.len() < 32 {
+            return Err(EchoError::NotEnoughRandomBytes);
+        }
+        PrivateKey::from_bytes(&random_bytes[..32])
+    }
+
+    // This function derives the X25519 public key, publicKey = privateKey * B
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey(MontgomeryPoint::mul_base_clamped(self.0).to_bytes())
+    }
+
+    // This function performs the Diffie-Hellman key exchange using X25519
+    // The shared secret is wiped when the returned value is dropped
+    // A low order public key gives an all zero output whatever our private key is, it fails with LowOrderPublicKey
+    // so a malicious peer or server cannot force a known shared secret (contributory behavior, RFC 7748 section 6.1)
+    pub fn diffie_hellman(&self, their_public_key: &PublicKey) -> Result<Zeroizing<[u8; 32]>, EchoError> {
+        // The clamped scalar is used as is, reducing it mod L first gives wrong results for points outside the prime order subgroup
+        let shared_secret = Zeroizing::new(MontgomeryPoint(their_public_key.0).mul_clamped(self.0).to_bytes());
+
+        // OR all bytes together instead of comparing, so the check does not stop at the first non zero byte
+        if shared_secret.iter().fold(0u8, |acc, byte| acc | byte) == 0 {
+            return Err(EchoError::LowOrderPublicKey);
+        }
+
+        Ok(shared_secret)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    // This function copies the secret out, the caller is responsible for wiping the copy
+    pub fn to_bytes(&self) -> Zeroizing<[u8; 32]> {
+        Zeroizing::new(self.0)
+    }
+}
+
+impl PublicKey {
+    // This function builds a public key from exactly 32 bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<PublicKey, EchoError> {
+        let public_key = bytes.try_into().map_err(|_| EchoError::InvalidPublicKey)?;
+        Ok(PublicKey(public_key))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0
+    }
+}
+
+impl From<[u8; 32]> for PublicKey {
+    fn from(bytes: [u8; 32]) -> PublicKey {
+        PublicKey(bytes)
+    }
+}
+
+impl KeyPair {
+    // This function generates a key pair from 32 random bytes
+    pub fn from_random_bytes(random_bytes: &[u8]) -> Result<KeyPair, EchoError> {
+        let private_key = PrivateKey::from_random_bytes(random_bytes)?;
+        let public_key = private_key.public_key();
+        Ok(KeyPair { private_key, public_key })
+    }
+
+    // This function generates a key pair from the platform random number generator
+    pub fn generate() -> Result<KeyPair, EchoError> {
+        KeyPair::generate_with(&mut OsRng)
+    }
+
+    // This function generates a key pair from the given random number generator
+    pub fn generate_with<R: RngCore + CryptoRng + ?Sized>(rng: &mut R) -> Result<KeyPair, EchoError> {
+        let mut random_bytes = Zeroizing::new([0u8; 32]);
+        rng::fill(rng, random_bytes.as_mut())?;
+
+        let private_key = PrivateKey::from_array(*random_bytes);
+        let public_key = private_key.public_key();
+        Ok(KeyPair { private_key, public_key })
+    }
+}
+
+// This function generates a 32 byte Ed25519 seed from the platform random number generator
+pub fn generate_ed25519_seed() -> Result<Zeroizing<[u8; 32]>, EchoError> {
+    generate_ed25519_seed_with(&mut OsRng)
+}
+
+// This function generates a 32 byte Ed25519 seed from the given random number generator
+pub fn generate_ed25519_seed_with<R: RngCore + CryptoRng + ?Sized>(rng: &mut R) -> Result<Zeroizing<[u8; 32]>, EchoError> {
+    let mut seed = Zeroizing::new([0u8; 32]);
+    rng::fill(rng, seed.as_mut())?;
+    Ok(seed)
+}
+
+// This function computes the clamped scalar used by Ed25519 from a 32 byte seed (first half of SHA-512(seed))
+pub fn ed25519_scalar_from_seed(seed: &[u8]) -> Zeroizing<[u8; 32]> {
+    let mut hash = Sha512::digest(seed);
+
+    let mut scalar_bytes = Zeroizing::new([0u8; 32]);
+    scalar_bytes.copy_from_slice(&hash[..32]);
+    clamp(&mut scalar_bytes);
+    hash.zeroize();
+
+    scalar_bytes
+}
+
+// This function generates a public key from a seed using the ED25519 algorithm
+pub fn ed25519_public_key_from_seed(seed: &[u8]) -> [u8; 32] {
+    let scalar = Zeroizing::new(Scalar::from_bytes_mod_order(*ed25519_scalar_from_seed(seed)));
+    (*scalar * ED25519_BASEPOINT_POINT).compress().to_bytes()
+}
+
+// This function derives the X25519 key pair that shares its scalar with an Ed25519 seed
+pub fn x25519_from_ed25519_seed(seed: &[u8]) -> KeyPair {
+    let private_key = PrivateKey::from_array(*ed25519_scalar_from_seed(seed));
+    let public_key = private_key.public_key();
+    KeyPair { private_key, public_key }
+}
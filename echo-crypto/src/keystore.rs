@@ -0,0 +1,429 @@
+use crate::aead;
+use crate::codec::Reader;
+use crate::bundle::{PreKeyBundle, VerifiedPreKeyBundle};
+use crate::error::EchoError;
+use crate::kdf;
+use crate::keys::{KeyPair, PublicKey};
+use crate::prekeys::{OneTimePreKey, OneTimePreKeyBatch, SignedPreKey};
+use crate::ratchet::RatchetSession;
+use crate::rng::{CryptoRng, OsRng, RngCore};
+use crate::trust::TrustedIdentity;
+use crate::x3dh;
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+// Opaque reference to a key held by a KeyStore, handles are never reused within a store
+pub type KeyHandle = u32;
+
+// Key store serialization layout (all integers big endian), this is only ever written inside an encrypted envelope:
+// VERSION (1 byte) || NEXT_HANDLE (4 bytes) || HAS_IK (1 byte, 0 or 1) [|| IK_PRIVATE (32 bytes)]
+// || SPK_COUNT (4 bytes) || (SPK_ID (4 bytes) || SPK_PRIVATE (32 bytes) || SPK_SIGNATURE (64 bytes)) * SPK_COUNT
+// || OPK_COUNT (4 bytes) || (OPK_ID (4 bytes) || OPK_PRIVATE (32 bytes)) * OPK_COUNT
+// || KEY_COUNT (4 bytes) || (HANDLE (4 bytes) || KIND (1 byte) || KEY (32 bytes)) * KEY_COUNT
+pub const KEYSTORE_VERSION: u8 = 1;
+
+// Associated data binding exported envelopes to their purpose
+const KEYSTORE_AAD: &[u8] = b"EchoKeyStore";
+
+const KIND_KEY_PAIR: u8 = 0;
+const KIND_SECRET: u8 = 1;
+
+// A key referenced by handle: an X25519 key pair (ephemeral keys) or a 32 byte secret (X3DH outputs, AES keys)
+#[derive(Clone)]
+enum StoredKey {
+    KeyPair(KeyPair),
+    Secret(Zeroizing<[u8; 32]>),
+}
+
+// This struct is the outcome of an X3DH run inside the store: SK stays in the store, only its handle and AD leave it
+// The initiator's result also records the signed prekey of the bundle, it is private so only the store can set it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StoredX3dh {
+    pub secret: KeyHandle,
+    pub associated_data: [u8; 64],
+    remote_signed_prekey: Option<PublicKey>,
+}
+
+impl StoredX3dh {
+    // This function returns the signed prekey of the bundle given to x3dh_initiate, None for a responder result
+    pub fn remote_signed_prekey(&self) -> Option<&PublicKey> {
+        self.remote_signed_prekey.as_ref()
+    }
+}
+
+// This struct owns every private key of a user, callers only ever see public keys and handles
+// The contents can only leave the store as an AES-GCM envelope, see export and import
+#[derive(Clone)]
+pub struct KeyStore {
+    identity: Option<KeyPair>,
+    signed_prekeys: BTreeMap<u32, SignedPreKey>,
+    one_time_prekeys: BTreeMap<u32, OneTimePreKey>,
+    keys: BTreeMap<KeyHandle, StoredKey>,
+    next_handle: KeyHandle,
+}
+
+// Handles start at 1 so JavaScript callers can treat 0 as "no key"
+impl Default for KeyStore {
+    fn default() -> KeyStore {
+        KeyStore {
+            identity: None,
+            signed_prekeys: BTreeMap::new(),
+            one_time_prekeys: BTreeMap::new(),
+            keys: BTreeMap::new(),
+            next_handle: 1,
+        }
+    }
+}
+
+impl KeyStore {
+    pub fn new() -> KeyStore {
+        KeyStore::default()
+    }
+
+    // This function generates a new identity key pair, replacing any previous one
+    pub fn generate_identity(&mut self) -> Result<PublicKey, EchoError> {
+        self.generate_identity_with(&mut OsRng)
+    }
+
+    pub fn generate_identity_with<R: RngCore + CryptoRng + ?Sized>(&mut self, rng: &mut R) -> Result<PublicKey, EchoError> {
+        let identity = KeyPair::generate_with(rng)?;
+        let public_key = identity.public_key;
+        self.identity = Some(identity);
+        Ok(public_key)
+    }
+
+    pub fn identity_public_key(&self) -> Result<PublicKey, EchoError> {
+        Ok(self.identity()?.public_key)
+    }
+
+    // This function generates a signed prekey with the given ID and signs it with the identity key
+    pub fn generate_signed_prekey(&mut self, id: u32) -> Result<PublicKey, EchoError> {
+        self.generate_signed_prekey_with(id, &mut OsRng)
+    }
+
+    pub fn generate_signed_prekey_with<R: RngCore + CryptoRng + ?Sized>(&mut self, id: u32, rng: &mut R) -> Result<PublicKey, EchoError> {
+        let signed_prekey = SignedPreKey::generate_with(id, &self.identity()?.private_key, rng)?;
+        let public_key = signed_prekey.key_pair.public_key;
+        self.signed_prekeys.insert(id, signed_prekey);
+        Ok(public_key)
+    }
+
+    // This function generates `count` one-time prekeys numbered from `start_id` and keeps their private halves
+    // Returns the public half in the upload encoding of OneTimePreKeyBatch::public_bytes
+    pub fn generate_one_time_prekeys(&mut self, start_id: u32, count: u32) -> Result<Vec<u8>, EchoError> {
+        self.generate_one_time_prekeys_with(start_id, count, &mut OsRng)
+    }
+
+    pub fn generate_one_time_prekeys_with<R: RngCore + CryptoRng + ?Sized>(
+        &mut self,
+        start_id: u32,
+        count: u32,
+        rng: &mut R,
+    ) -> Result<Vec<u8>, EchoError> {
+        let batch = OneTimePreKeyBatch::generate_with(start_id, count, rng)?;
+        for prekey in batch.prekeys() {
+            self.one_time_prekeys.insert(prekey.id, prekey.clone());
+        }
+
+        Ok(batch.public_bytes())
+    }
+
+    pub fn one_time_prekey_count(&self) -> usize {
+        self.one_time_prekeys.len()
+    }
+
+    // This function builds the bundle to publish for a signed prekey and optionally one of the one-time prekeys
+    pub fn prekey_bundle(&self, signed_prekey_id: u32, one_time_prekey_id: Option<u32>) -> Result<PreKeyBundle, EchoError> {
+        let signed_prekey = self.signed_prekeys.get(&signed_prekey_id).ok_or(EchoError::KeyNotFound)?;
+        let one_time_prekey = match one_time_prekey_id {
+            Some(id) => Some(self.one_time_prekeys.get(&id).ok_or(EchoError::KeyNotFound)?),
+            None => None,
+        };
+
+        Ok(PreKeyBundle::new(self.identity_public_key()?, signed_prekey, one_time_prekey))
+    }
+
+    // This function generates an ephemeral key pair and returns its handle
+    pub fn generate_ephemeral_key(&mut self) -> Result<KeyHandle, EchoError> {
+        self.generate_ephemeral_key_with(&mut OsRng)
+    }
+
+    pub fn generate_ephemeral_key_with<R: RngCore + CryptoRng + ?Sized>(&mut self, rng: &mut R) -> Result<KeyHandle, EchoError> {
+        let key_pair = KeyPair::generate_with(rng)?;
+        self.insert(StoredKey::KeyPair(key_pair))
+    }
+
+    // This function generates a random 256-bit AES-GCM key and returns its handle
+    pub fn generate_symmetric_key(&mut self) -> Result<KeyHandle, EchoError> {
+        self.generate_symmetric_key_with(&mut OsRng)
+    }
+
+    pub fn generate_symmetric_key_with<R: RngCore + CryptoRng + ?Sized>(&mut self, rng: &mut R) -> Result<KeyHandle, EchoError> {
+        let key = aead::generate_key_with(rng)?;
+        self.insert(StoredKey::Secret(key))
+    }
+
+    // This function returns the public key of a key pair handle
+    pub fn public_key(&self, handle: KeyHandle) -> Result<PublicKey, EchoError> {
+        match self.keys.get(&handle) {
+            Some(StoredKey::KeyPair(key_pair)) => Ok(key_pair.public_key),
+            _ => Err(EchoError::KeyNotFound),
+        }
+    }
+
+    // This function derives a message key from a stored secret with HKDF (see kdf::derive_symmetric_key)
+    pub fn derive_symmetric_key(&mut self, secret: KeyHandle) -> Result<KeyHandle, EchoError> {
+        let key = kdf::derive_symmetric_key(self.secret(secret)?);
+        self.insert(StoredKey::Secret(key))
+    }
+
+    // This function wipes the key behind a handle, unknown handles are ignored
+    pub fn remove(&mut self, handle: KeyHandle) {
+        self.keys.remove(&handle);
+    }
+
+    pub fn contains(&self, handle: KeyHandle) -> bool {
+        self.keys.contains_key(&handle)
+    }
+
+    // This function runs X3DH as the initiator with the identity key and an ephemeral key from this store
+    // The bundle's identity key must be the one accepted by the trust store, see x3dh::initiate
+    // The ephemeral key is single use and is wiped afterwards, its public half must be sent with the first message
+    pub fn x3dh_initiate(
+        &mut self,
+        bundle: &VerifiedPreKeyBundle,
+        their_identity: &TrustedIdentity,
+        ephemeral: KeyHandle,
+    ) -> Result<StoredX3dh, EchoError> {
+        let identity = self.identity()?;
+        let result = match self.keys.get(&ephemeral) {
+            Some(StoredKey::KeyPair(key_pair)) => x3dh::initiate(bundle, their_identity, &identity.private_key, &key_pair.private_key)?,
+            _ => return Err(EchoError::KeyNotFound),
+        };
+
+        self.keys.remove(&ephemeral);
+        self.insert_x3dh(&result, Some(bundle.bundle().signed_prekey))
+    }
+
+    // This function runs X3DH as the responder with the prekeys named in the initiator's first message
+    // The initiator's identity key is the token returned by the trust store for that contact
+    // The one-time prekey is consumed so it can never be used for a second session
+    pub fn x3dh_respond(
+        &mut self,
+        signed_prekey_id: u32,
+        one_time_prekey_id: Option<u32>,
+        their_identity: &TrustedIdentity,
+        their_ephemeral_public: &PublicKey,
+    ) -> Result<StoredX3dh, EchoError> {
+        let identity = self.identity()?;
+        let signed_prekey = self.signed_prekeys.get(&signed_prekey_id).ok_or(EchoError::KeyNotFound)?;
+        let one_time_prekey = match one_time_prekey_id {
+            Some(id) => Some(self.one_time_prekeys.get(&id).ok_or(EchoError::KeyNotFound)?),
+            None => None,
+        };
+
+        let result = x3dh::respond(
+            &identity.private_key,
+            &signed_prekey.key_pair.private_key,
+            one_time_prekey.map(|prekey| &prekey.private_key),
+            their_identity,
+            their_ephemeral_public,
+        )?;
+
+        if let Some(id) = one_time_prekey_id {
+            self.one_time_prekeys.remove(&id);
+        }
+        self.insert_x3dh(&result, None)
+    }
+
+    // This function starts the Double Ratchet as the initiator from an X3DH result of this store, SK never leaves the store
+    // The remote ratchet key is the signed prekey of the bundle given to x3dh_initiate, a responder result has none
+    // SK is wiped once the session is built so it can only ever seed one session
+    pub fn ratchet_initiate(&mut self, x3dh: &StoredX3dh, header_encryption: bool) -> Result<RatchetSession, EchoError> {
+        let remote_ratchet_public = x3dh.remote_signed_prekey.as_ref().ok_or(EchoError::KeyNotFound)?;
+        let shared_secret = self.secret(x3dh.secret)?;
+        let session = if header_encryption {
+            RatchetSession::new_initiator_with_header_encryption(shared_secret, remote_ratchet_public, &x3dh.associated_data)?
+        } else {
+            RatchetSession::new_initiator(shared_secret, remote_ratchet_public, &x3dh.associated_data)?
+        };
+
+        self.keys.remove(&x3dh.secret);
+        Ok(session)
+    }
+
+    // This function starts the Double Ratchet as the responder, the ratchet key pair is the signed prekey given to x3dh_respond
+    // The signed prekey stays in the store, SK is wiped like in ratchet_initiate
+    pub fn ratchet_respond(&mut self, x3dh: &StoredX3dh, signed_prekey_id: u32, header_encryption: bool) -> Result<RatchetSession, EchoError> {
+        let shared_secret = self.secret(x3dh.secret)?;
+        let signed_prekey = self.signed_prekeys.get(&signed_prekey_id).ok_or(EchoError::KeyNotFound)?;
+        let key_pair = signed_prekey.key_pair.clone();
+        let session = if header_encryption {
+            RatchetSession::new_responder_with_header_encryption(shared_secret, key_pair, &x3dh.associated_data)
+        } else {
+            RatchetSession::new_responder(shared_secret, key_pair, &x3dh.associated_data)
+        };
+
+        self.keys.remove(&x3dh.secret);
+        Ok(session)
+    }
+
+    // This function works like aead::seal_envelope with the key behind the handle
+    pub fn seal(&self, key: KeyHandle, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, EchoError> {
+        aead::seal_envelope(plaintext, self.secret(key)?, aad)
+    }
+
+    // This function works like aead::open_envelope with the key behind the handle
+    pub fn open(&self, key: KeyHandle, envelope: &[u8], aad: &[u8]) -> Result<Vec<u8>, EchoError> {
+        aead::open_envelope(envelope, self.secret(key)?, aad)
+    }
+
+    // This function encrypts the whole store under a 32 byte wrapping key for persistence
+    // Only for Rust callers that keep the wrapping key secret, the wasm binding persists the store through a Vault instead
+    pub fn export(&self, wrapping_key: &[u8]) -> Result<Vec<u8>, EchoError> {
+        aead::seal_envelope(&self.to_bytes(), wrapping_key, KEYSTORE_AAD)
+    }
+
+    // This function restores a store written by export, a wrong key or a tampered blob fails with DecryptionFailed
+    pub fn import(blob: &[u8], wrapping_key: &[u8]) -> Result<KeyStore, EchoError> {
+        let bytes = Zeroizing::new(aead::open_envelope(blob, wrapping_key, KEYSTORE_AAD)?);
+        KeyStore::from_bytes(&bytes)
+    }
+
+    fn identity(&self) -> Result<&KeyPair, EchoError> {
+        self.identity.as_ref().ok_or(EchoError::KeyNotFound)
+    }
+
+    fn secret(&self, handle: KeyHandle) -> Result<&[u8; 32], EchoError> {
+        match self.keys.get(&handle) {
+            Some(StoredKey::Secret(secret)) => Ok(secret),
+            _ => Err(EchoError::KeyNotFound),
+        }
+    }
+
+    fn insert(&mut self, key: StoredKey) -> Result<KeyHandle, EchoError> {
+        let handle = self.next_handle;
+        self.next_handle = handle.checked_add(1).ok_or(EchoError::KeyStoreFull)?;
+        self.keys.insert(handle, key);
+        Ok(handle)
+    }
+
+    fn insert_x3dh(&mut self, result: &x3dh::X3dhResult, remote_signed_prekey: Option<PublicKey>) -> Result<StoredX3dh, EchoError> {
+        let secret = self.insert(StoredKey::Secret(Zeroizing::new(result.shared_secret)))?;
+        Ok(StoredX3dh { secret, associated_data: result.associated_data, remote_signed_prekey })
+    }
+
+    // The buffer is allocated at its final size, growing it would free copies of the private keys without wiping them
+    pub(crate) fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let length = 1
+            + 4
+            + 1
+            + if self.identity.is_some() { 32 } else { 0 }
+            + 4
+            + self.signed_prekeys.len() * (4 + 32 + 64)
+            + 4
+            + self.one_time_prekeys.len() * (4 + 32)
+            + 4
+            + self.keys.len() * (4 + 1 + 32);
+        let mut bytes = Zeroizing::new(Vec::with_capacity(length));
+        let capacity = bytes.capacity();
+
+        bytes.push(KEYSTORE_VERSION);
+        bytes.extend_from_slice(&self.next_handle.to_be_bytes());
+
+        match &self.identity {
+            Some(identity) => {
+                bytes.push(1);
+                bytes.extend_from_slice(identity.private_key.as_bytes());
+            }
+            None => bytes.push(0),
+        }
+
+        bytes.extend_from_slice(&(self.signed_prekeys.len() as u32).to_be_bytes());
+        for (id, signed_prekey) in &self.signed_prekeys {
+            bytes.extend_from_slice(&id.to_be_bytes());
+            bytes.extend_from_slice(signed_prekey.key_pair.private_key.as_bytes());
+            bytes.extend_from_slice(&signed_prekey.signature);
+        }
+
+        bytes.extend_from_slice(&(self.one_time_prekeys.len() as u32).to_be_bytes());
+        for (id, prekey) in &self.one_time_prekeys {
+            bytes.extend_from_slice(&id.to_be_bytes());
+            bytes.extend_from_slice(prekey.private_key.as_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.keys.len() as u32).to_be_bytes());
+        for (handle, key) in &self.keys {
+            bytes.extend_from_slice(&handle.to_be_bytes());
+            match key {
+                StoredKey::KeyPair(key_pair) => {
+                    bytes.push(KIND_KEY_PAIR);
+                    bytes.extend_from_slice(key_pair.private_key.as_bytes());
+                }
+                StoredKey::Secret(secret) => {
+                    bytes.push(KIND_SECRET);
+                    bytes.extend_from_slice(secret.as_ref());
+                }
+            }
+        }
+
+        debug_assert_eq!(bytes.capacity(), capacity);
+        debug_assert_eq!(bytes.len(), length);
+        bytes
+    }
+
+    // Public keys are not stored, they are recomputed from the private halves
+    // A duplicate ID or handle is rejected, otherwise the blob would decode to a different store than the one it encodes
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<KeyStore, EchoError> {
+        let mut reader = Reader::new(bytes, EchoError::MalformedKeyStore);
+
+        if reader.u8()? != KEYSTORE_VERSION {
+            return Err(EchoError::UnsupportedVersion);
+        }
+
+        let mut store = KeyStore::new();
+        store.next_handle = reader.u32()?;
+
+        store.identity = match reader.u8()? {
+            0 => None,
+            1 => Some(reader.key_pair()?),
+            _ => return Err(EchoError::MalformedKeyStore),
+        };
+
+        for _ in 0..reader.u32()? {
+            let id = reader.u32()?;
+            let key_pair = reader.key_pair()?;
+            let signature = reader.array()?;
+            if store.signed_prekeys.insert(id, SignedPreKey { id, key_pair, signature }).is_some() {
+                return Err(EchoError::MalformedKeyStore);
+            }
+        }
+
+        for _ in 0..reader.u32()? {
+            let id = reader.u32()?;
+            let KeyPair { private_key, public_key } = reader.key_pair()?;
+            if store.one_time_prekeys.insert(id, OneTimePreKey { id, private_key, public_key }).is_some() {
+                return Err(EchoError::MalformedKeyStore);
+            }
+        }
+
+        for _ in 0..reader.u32()? {
+            let handle = reader.u32()?;
+            if handle == 0 || handle >= store.next_handle {
+                return Err(EchoError::MalformedKeyStore);
+            }
+
+            let key = match reader.u8()? {
+                KIND_KEY_PAIR => StoredKey::KeyPair(reader.key_pair()?),
+                KIND_SECRET => StoredKey::Secret(Zeroizing::new(reader.array()?)),
+                _ => return Err(EchoError::MalformedKeyStore),
+            };
+            if store.keys.insert(handle, key).is_some() {
+                return Err(EchoError::MalformedKeyStore);
+            }
+        }
+
+        reader.finish()?;
+        Ok(store)
+    }
+}
@@ -0,0 +1,33 @@
+// Echo cryptographic core
+// Pure Rust implementation of the primitives and protocols used by Echo, the wasm crates
+// (aes-wasm, dh-wasm, xeddsa-wasm, ratchet-wasm) are thin binding layers on top of it
+
+pub mod aead;
+pub mod bundle;
+mod codec;
+pub mod error;
+pub mod fingerprint;
+pub mod kdf;
+pub mod keys;
+pub mod keystore;
+pub mod prekeys;
+pub mod ratchet;
+pub mod rng;
+pub mod session;
+pub mod trust;
+pub mod vault;
+pub mod x3dh;
+pub mod xeddsa;
+
+pub use bundle::{PreKeyBundle, VerifiedPreKeyBundle};
+pub use error::EchoError;
+pub use fingerprint::Fingerprint;
+pub use keys::{KeyPair, PrivateKey, PublicKey};
+pub use keystore::{KeyHandle, KeyStore, StoredX3dh};
+pub use prekeys::{OneTimePreKey, OneTimePreKeyBatch, SignedPreKey};
+pub use ratchet::{MessageHeader, RatchetSession};
+pub use rng::{CryptoRng, OsRng, RngCore};
+pub use session::SessionRecord;
+pub use trust::{TrustStatus, TrustStore, TrustedIdentity};
+pub use vault::{Argon2Params, Vault};
+pub use x3dh::X3dhResult;
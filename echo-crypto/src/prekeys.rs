@@ -0,0 +1,120 @@
+use crate::error::EchoError;
+use crate::keys::{KeyPair, PrivateKey, PublicKey};
+use crate::rng::{self, CryptoRng, OsRng, RngCore};
+use crate::xeddsa;
+use zeroize::Zeroizing;
+
+// Maximum number of one-time prekeys generated in a single batch
+pub const MAX_ONE_TIME_PREKEY_BATCH: u32 = 100;
+
+// This struct is a single numbered one-time prekey, the private half stays with the owner
+#[derive(Clone)]
+pub struct OneTimePreKey {
+    pub id: u32,
+    pub private_key: PrivateKey,
+    pub public_key: PublicKey,
+}
+
+// This struct is a batch of one-time prekeys with consecutive IDs
+#[derive(Clone)]
+pub struct OneTimePreKeyBatch {
+    prekeys: Vec<OneTimePreKey>,
+}
+
+impl OneTimePreKeyBatch {
+    // This function generates `count` one-time prekeys numbered from `start_id`
+    // 32 random bytes are consumed per prekey
+    pub fn generate(start_id: u32, count: u32, random_bytes: &[u8]) -> Result<OneTimePreKeyBatch, EchoError> {
+        if count == 0 || count > MAX_ONE_TIME_PREKEY_BATCH {
+            return Err(EchoError::InvalidPreKeyBatch);
+        }
+
+        if start_id.checked_add(count - 1).is_none() {
+            return Err(EchoError::InvalidPreKeyBatch);
+        }
+
+        if random_bytes.len() < 32 * count as usize {
+            return Err(EchoError::NotEnoughRandomBytes);
+        }
+
+        let prekeys = random_bytes
+            .chunks_exact(32)
+            .take(count as usize)
+            .zip(start_id..)
+            .map(|(chunk, id)| {
+                let KeyPair { private_key, public_key } = KeyPair::from_random_bytes(chunk)?;
+                Ok(OneTimePreKey { id, private_key, public_key })
+            })
+            .collect::<Result<Vec<_>, EchoError>>()?;
+
+        Ok(OneTimePreKeyBatch { prekeys })
+    }
+
+    // This function generates `count` one-time prekeys numbered from `start_id` from the given random number generator
+    pub fn generate_with<R: RngCore + CryptoRng + ?Sized>(start_id: u32, count: u32, rng: &mut R) -> Result<OneTimePreKeyBatch, EchoError> {
+        if count > MAX_ONE_TIME_PREKEY_BATCH {
+            return Err(EchoError::InvalidPreKeyBatch);
+        }
+
+        let mut random_bytes = Zeroizing::new(vec![0u8; 32 * count as usize]);
+        rng::fill(rng, &mut random_bytes)?;
+        OneTimePreKeyBatch::generate(start_id, count, &random_bytes)
+    }
+
+    pub fn prekeys(&self) -> &[OneTimePreKey] {
+        &self.prekeys
+    }
+
+    // This function encodes the public half of the batch for upload
+    // COUNT (4 bytes BE) || (ID (4 bytes BE) || OPK (32 bytes)) * COUNT
+    pub fn public_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + 36 * self.prekeys.len());
+        bytes.extend_from_slice(&(self.prekeys.len() as u32).to_be_bytes());
+        for prekey in &self.prekeys {
+            bytes.extend_from_slice(&prekey.id.to_be_bytes());
+            bytes.extend_from_slice(prekey.public_key.as_bytes());
+        }
+        bytes
+    }
+}
+
+// This struct is the medium term signed prekey, its public half is signed with the identity key using XEdDSA
+#[derive(Clone)]
+pub struct SignedPreKey {
+    pub id: u32,
+    pub key_pair: KeyPair,
+    pub signature: [u8; 64],
+}
+
+impl SignedPreKey {
+    // This function signs the public half of the key pair with the identity private key
+    // random must be 64 fresh random bytes (Z in the XEdDSA spec)
+    pub fn sign(id: u32, key_pair: KeyPair, identity_private: &PrivateKey, random: &[u8; 64]) -> SignedPreKey {
+        let signature = xeddsa::sign(identity_private, key_pair.public_key.as_bytes(), random);
+        SignedPreKey { id, key_pair, signature }
+    }
+
+    // This function generates a new signed prekey from the platform random number generator
+    pub fn generate(id: u32, identity_private: &PrivateKey) -> Result<SignedPreKey, EchoError> {
+        SignedPreKey::generate_with(id, identity_private, &mut OsRng)
+    }
+
+    // This function generates a new signed prekey and its signature nonce from the given random number generator
+    pub fn generate_with<R: RngCore + CryptoRng + ?Sized>(
+        id: u32,
+        identity_private: &PrivateKey,
+        rng: &mut R,
+    ) -> Result<SignedPreKey, EchoError> {
+        let key_pair = KeyPair::generate_with(rng)?;
+
+        let mut random = Zeroizing::new([0u8; 64]);
+        rng::fill(rng, random.as_mut())?;
+
+        Ok(SignedPreKey::sign(id, key_pair, identity_private, &random))
+    }
+
+    // This function checks the signature against the identity public key of the owner
+    pub fn verify(&self, identity_public: &PublicKey) -> bool {
+        xeddsa::verify_strict(identity_public, self.key_pair.public_key.as_bytes(), &self.signature)
+    }
+}
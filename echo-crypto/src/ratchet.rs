@@ -0,0 +1,649 @@
+use std::collections::VecDeque;
+use std::mem;
+
+use crate::aead::{aes_gcm_decrypt, aes_gcm_encrypt, NONCE_LENGTH, TAG_LENGTH};
+use crate::codec::Reader;
+use crate::error::EchoError;
+use crate::kdf::{hkdf_sha256, hmac_sha256};
+use crate::keys::{KeyPair, PublicKey};
+use crate::rng::{self, CryptoRng, OsRng, RngCore};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+// Info strings used to separate the different HKDF derivations of the ratchet
+const ROOT_KDF_INFO: &[u8] = b"EchoProtocolRatchet";
+const MESSAGE_KDF_INFO: &[u8] = b"EchoProtocolMessageKeys";
+const HEADER_KDF_INFO: &[u8] = b"EchoProtocolHeaderKeys";
+
+// Maximum number of message keys that can be skipped in a single receiving chain
+pub const MAX_SKIP: u32 = 1000;
+
+// Maximum number of skipped message keys kept per session, the oldest ones are dropped first
+pub const MAX_SKIPPED_KEYS: usize = 2000;
+
+// Header layout: DH (32 bytes) || PN (4 bytes BE) || N (4 bytes BE)
+pub const HEADER_LENGTH: usize = 40;
+
+// Encrypted header layout, used with header encryption: NONCE (12 bytes) || AES-GCM(HEADER) (40 bytes) || TAG (16 bytes)
+pub const ENCRYPTED_HEADER_LENGTH: usize = NONCE_LENGTH + HEADER_LENGTH + TAG_LENGTH;
+
+// This struct is the header sent alongside every ratchet message
+// dh is the sender's current ratchet public key, pn the length of the previous sending chain and n the message number
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageHeader {
+    pub dh: PublicKey,
+    pub pn: u32,
+    pub n: u32,
+}
+
+impl MessageHeader {
+    // This function encodes the header into its fixed 40 byte form
+    pub fn to_bytes(&self) -> [u8; HEADER_LENGTH] {
+        let mut bytes = [0u8; HEADER_LENGTH];
+        bytes[0..32].copy_from_slice(self.dh.as_bytes());
+        bytes[32..36].copy_from_slice(&self.pn.to_be_bytes());
+        bytes[36..40].copy_from_slice(&self.n.to_be_bytes());
+        bytes
+    }
+
+    // This function decodes a header from the first 40 bytes of a message
+    pub fn from_bytes(bytes: &[u8]) -> Result<MessageHeader, EchoError> {
+        if bytes.len() < HEADER_LENGTH {
+            return Err(EchoError::MalformedMessageHeader);
+        }
+
+        let dh = PublicKey::from_bytes(&bytes[0..32])?;
+        let pn = u32::from_be_bytes(bytes[32..36].try_into().unwrap());
+        let n = u32::from_be_bytes(bytes[36..40].try_into().unwrap());
+
+        Ok(MessageHeader { dh, pn, n })
+    }
+}
+
+// KDF_RK, RK, CK, NHK = HKDF(salt = RK, IKM = DH output), 96 bytes split into the new root key, a chain key and a next header key
+// HKDF output is a prefix of any longer output, so RK and CK are the same as when only 64 bytes were derived
+// The next header key is only used by sessions with header encryption
+type RootKdfOutput = (Zeroizing<[u8; 32]>, Zeroizing<[u8; 32]>, Zeroizing<[u8; 32]>);
+
+fn kdf_rk(root_key: &[u8; 32], dh_out: &[u8; 32]) -> RootKdfOutput {
+    let okm = hkdf_sha256(dh_out, root_key, ROOT_KDF_INFO, 96).expect("96 bytes is a valid HKDF-SHA256 output length");
+
+    let mut new_root_key = Zeroizing::new([0u8; 32]);
+    let mut chain_key = Zeroizing::new([0u8; 32]);
+    let mut next_header_key = Zeroizing::new([0u8; 32]);
+    new_root_key.copy_from_slice(&okm[0..32]);
+    chain_key.copy_from_slice(&okm[32..64]);
+    next_header_key.copy_from_slice(&okm[64..96]);
+
+    (new_root_key, chain_key, next_header_key)
+}
+
+// This function derives the two header keys both sides share after X3DH from SK
+// The initiator sends its first chain under the first one, the responder's first chain is the next header key of both
+fn shared_header_keys(shared_secret: &[u8; 32]) -> (Zeroizing<[u8; 32]>, Zeroizing<[u8; 32]>) {
+    let okm = hkdf_sha256(shared_secret, &[0u8; 32], HEADER_KDF_INFO, 64).expect("64 bytes is a valid HKDF-SHA256 output length");
+
+    let mut header_key = Zeroizing::new([0u8; 32]);
+    let mut next_header_key = Zeroizing::new([0u8; 32]);
+    header_key.copy_from_slice(&okm[0..32]);
+    next_header_key.copy_from_slice(&okm[32..64]);
+
+    (header_key, next_header_key)
+}
+
+// HENCRYPT, the header is sealed under a fresh random nonce since a header key is used for a whole chain
+fn encrypt_header(header_key: &[u8; 32], header: &MessageHeader, associated_data: &[u8]) -> Result<Vec<u8>, EchoError> {
+    let mut nonce = [0u8; NONCE_LENGTH];
+    rng::fill(&mut OsRng, &mut nonce)?;
+
+    let mut encrypted_header = Vec::with_capacity(ENCRYPTED_HEADER_LENGTH);
+    encrypted_header.extend_from_slice(&nonce);
+    encrypted_header.extend_from_slice(&aes_gcm_encrypt(&header.to_bytes(), header_key, &nonce, associated_data)?);
+    Ok(encrypted_header)
+}
+
+// HDECRYPT, returns None when the header was not encrypted under this header key
+fn decrypt_header(header_key: &[u8; 32], encrypted_header: &[u8], associated_data: &[u8]) -> Option<MessageHeader> {
+    let (nonce, ciphertext) = encrypted_header.split_at(NONCE_LENGTH);
+    let header_bytes = aes_gcm_decrypt(ciphertext, header_key, nonce, associated_data).ok()?;
+    MessageHeader::from_bytes(&header_bytes).ok()
+}
+
+// KDF_CK, MK = HMAC(CK, 0x01) and the next CK = HMAC(CK, 0x02)
+fn kdf_ck(chain_key: &[u8; 32]) -> (Zeroizing<[u8; 32]>, Zeroizing<[u8; 32]>) {
+    (hmac_sha256(chain_key, &[0x02]), hmac_sha256(chain_key, &[0x01]))
+}
+
+// This function expands a message key into the AES-256 key and the 96-bit nonce used for that single message
+// A message key is only ever used once so a nonce derived from it is never repeated
+fn message_key_material(message_key: &[u8; 32]) -> (Zeroizing<[u8; 32]>, [u8; 12]) {
+    let okm = hkdf_sha256(message_key, &[0u8; 32], MESSAGE_KDF_INFO, 44).expect("44 bytes is a valid HKDF-SHA256 output length");
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    let mut nonce = [0u8; 12];
+    key.copy_from_slice(&okm[0..32]);
+    nonce.copy_from_slice(&okm[32..44]);
+
+    (key, nonce)
+}
+
+// This struct is the key of one message that has not arrived yet
+// chain is the ratchet public key of its chain, or the header key of its chain when headers are encrypted
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+struct SkippedMessageKey {
+    chain: [u8; 32],
+    message_number: u32,
+    message_key: [u8; 32],
+}
+
+// This struct holds the message keys of messages that have not arrived yet, oldest first
+// Keys are indexed by (chain, message number) and are deleted as soon as they are used
+// There are at most MAX_SKIPPED_KEYS of them so they are searched linearly, which also lets header keys be wiped
+#[derive(Clone, Default)]
+struct SkippedMessageKeys {
+    keys: VecDeque<SkippedMessageKey>,
+}
+
+impl SkippedMessageKeys {
+    fn insert(&mut self, chain: [u8; 32], message_number: u32, message_key: Zeroizing<[u8; 32]>) {
+        if self.keys.len() == MAX_SKIPPED_KEYS {
+            self.keys.pop_front();
+        }
+
+        self.keys.push_back(SkippedMessageKey { chain, message_number, message_key: *message_key });
+    }
+
+    fn contains(&self, chain: &[u8; 32], message_number: u32) -> bool {
+        self.keys.iter().any(|key| key.chain == *chain && key.message_number == message_number)
+    }
+
+    fn take(&mut self, chain: &[u8; 32], message_number: u32) -> Option<Zeroizing<[u8; 32]>> {
+        let position = self.keys.iter().position(|key| key.chain == *chain && key.message_number == message_number)?;
+        let skipped = self.keys.remove(position)?;
+        Some(Zeroizing::new(skipped.message_key))
+    }
+
+    // This function lists the distinct chains that still have skipped keys
+    fn chains(&self) -> Vec<&[u8; 32]> {
+        let mut chains: Vec<&[u8; 32]> = Vec::new();
+        for key in &self.keys {
+            if !chains.contains(&&key.chain) {
+                chains.push(&key.chain);
+            }
+        }
+        chains
+    }
+}
+
+// Header keys of the "Double Ratchet with header encryption" variant
+// sending and receiving (HKs, HKr) encrypt the headers of the current chains, the next keys (NHKs, NHKr) those of the
+// chains started by the next DH ratchet step, a header that only opens with NHKr tells the receiver to ratchet
+#[derive(Clone)]
+struct HeaderKeys {
+    sending: Option<Zeroizing<[u8; 32]>>,
+    receiving: Option<Zeroizing<[u8; 32]>>,
+    next_sending: Zeroizing<[u8; 32]>,
+    next_receiving: Zeroizing<[u8; 32]>,
+}
+
+// State of one Double Ratchet conversation, following the Signal Double Ratchet specification
+#[derive(Clone)]
+pub struct RatchetSession {
+    dh_self: KeyPair,
+    dh_remote: Option<PublicKey>,
+    root_key: Zeroizing<[u8; 32]>,
+    sending_chain_key: Option<Zeroizing<[u8; 32]>>,
+    receiving_chain_key: Option<Zeroizing<[u8; 32]>>,
+    sending_message_number: u32,
+    receiving_message_number: u32,
+    previous_chain_length: u32,
+    skipped_message_keys: SkippedMessageKeys,
+    associated_data: Vec<u8>,
+    header_keys: Option<HeaderKeys>,
+}
+
+impl RatchetSession {
+    // RatchetInitAlice, the initiator knows the responder's ratchet public key (their signed prekey) from X3DH
+    pub fn new_initiator(shared_secret: &[u8; 32], remote_ratchet_public: &PublicKey, associated_data: &[u8]) -> Result<RatchetSession, EchoError> {
+        RatchetSession::new_initiator_with(shared_secret, remote_ratchet_public, associated_data, &mut OsRng)
+    }
+
+    // The generator supplies the first ratchet key pair of the initiator
+    pub fn new_initiator_with<R: RngCore + CryptoRng + ?Sized>(
+        shared_secret: &[u8; 32],
+        remote_ratchet_public: &PublicKey,
+        associated_data: &[u8],
+        rng: &mut R,
+    ) -> Result<RatchetSession, EchoError> {
+        let (session, _) = RatchetSession::initiator(shared_secret, remote_ratchet_public, associated_data, rng)?;
+        Ok(session)
+    }
+
+    // RatchetInitAlice with header encryption, the shared header keys are derived from SK
+    pub fn new_initiator_with_header_encryption(
+        shared_secret: &[u8; 32],
+        remote_ratchet_public: &PublicKey,
+        associated_data: &[u8],
+    ) -> Result<RatchetSession, EchoError> {
+        RatchetSession::new_initiator_with_header_encryption_with(shared_secret, remote_ratchet_public, associated_data, &mut OsRng)
+    }
+
+    pub fn new_initiator_with_header_encryption_with<R: RngCore + CryptoRng + ?Sized>(
+        shared_secret: &[u8; 32],
+        remote_ratchet_public: &PublicKey,
+        associated_data: &[u8],
+        rng: &mut R,
+    ) -> Result<RatchetSession, EchoError> {
+        let (mut session, next_sending) = RatchetSession::initiator(shared_secret, remote_ratchet_public, associated_data, rng)?;
+        let (shared_header_key, shared_next_header_key) = shared_header_keys(shared_secret);
+
+        session.header_keys = Some(HeaderKeys {
+            sending: Some(shared_header_key),
+            receiving: None,
+            next_sending,
+            next_receiving: shared_next_header_key,
+        });
+        Ok(session)
+    }
+
+    // RatchetInitBob, the responder starts with the key pair of the ratchet public key the initiator used
+    pub fn new_responder(shared_secret: &[u8; 32], self_ratchet_key_pair: KeyPair, associated_data: &[u8]) -> RatchetSession {
+        RatchetSession {
+            dh_self: self_ratchet_key_pair,
+            dh_remote: None,
+            root_key: Zeroizing::new(*shared_secret),
+            sending_chain_key: None,
+            receiving_chain_key: None,
+            sending_message_number: 0,
+            receiving_message_number: 0,
+            previous_chain_length: 0,
+            skipped_message_keys: SkippedMessageKeys::default(),
+            associated_data: associated_data.to_vec(),
+            header_keys: None,
+        }
+    }
+
+    // RatchetInitBob with header encryption, the first message of the initiator opens with NHKr and starts the first DH ratchet step
+    pub fn new_responder_with_header_encryption(
+        shared_secret: &[u8; 32],
+        self_ratchet_key_pair: KeyPair,
+        associated_data: &[u8],
+    ) -> RatchetSession {
+        let mut session = RatchetSession::new_responder(shared_secret, self_ratchet_key_pair, associated_data);
+        let (shared_header_key, shared_next_header_key) = shared_header_keys(shared_secret);
+
+        session.header_keys = Some(HeaderKeys {
+            sending: None,
+            receiving: None,
+            next_sending: shared_next_header_key,
+            next_receiving: shared_header_key,
+        });
+        session
+    }
+
+    // This function builds the initiator state and also returns the next sending header key of its first chain
+    fn initiator<R: RngCore + CryptoRng + ?Sized>(
+        shared_secret: &[u8; 32],
+        remote_ratchet_public: &PublicKey,
+        associated_data: &[u8],
+        rng: &mut R,
+    ) -> Result<(RatchetSession, Zeroizing<[u8; 32]>), EchoError> {
+        let dh_self = KeyPair::generate_with(rng)?;
+        let dh_out = dh_self.private_key.diffie_hellman(remote_ratchet_public)?;
+        let (root_key, sending_chain_key, next_header_key) = kdf_rk(shared_secret, &dh_out);
+
+        let session = RatchetSession {
+            dh_self,
+            dh_remote: Some(*remote_ratchet_public),
+            root_key,
+            sending_chain_key: Some(sending_chain_key),
+            receiving_chain_key: None,
+            sending_message_number: 0,
+            receiving_message_number: 0,
+            previous_chain_length: 0,
+            skipped_message_keys: SkippedMessageKeys::default(),
+            associated_data: associated_data.to_vec(),
+            header_keys: None,
+        };
+        Ok((session, next_header_key))
+    }
+
+    // RatchetEncrypt, returns HEADER || CIPHERTEXT
+    // With header encryption the header is replaced by ENCRYPTED_HEADER, so the ratchet key and counters are not visible
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, EchoError> {
+        let chain_key = self.sending_chain_key.as_ref().ok_or(EchoError::SessionNotReady)?;
+        let (next_chain_key, message_key) = kdf_ck(chain_key);
+
+        let header = MessageHeader {
+            dh: self.dh_self.public_key,
+            pn: self.previous_chain_length,
+            n: self.sending_message_number,
+        };
+        let header_bytes = match &self.header_keys {
+            Some(header_keys) => {
+                let header_key = header_keys.sending.as_ref().ok_or(EchoError::SessionNotReady)?;
+                encrypt_header(header_key, &header, &self.associated_data)?
+            }
+            None => header.to_bytes().to_vec(),
+        };
+
+        let (key, nonce) = message_key_material(&message_key);
+        let ciphertext = aes_gcm_encrypt(plaintext, key.as_ref(), &nonce, &self.header_aad(&header_bytes))?;
+
+        self.sending_chain_key = Some(next_chain_key);
+        self.sending_message_number += 1;
+
+        let mut message = Vec::with_capacity(header_bytes.len() + ciphertext.len());
+        message.extend_from_slice(&header_bytes);
+        message.extend_from_slice(&ciphertext);
+
+        Ok(message)
+    }
+
+    // RatchetDecrypt, the session is only updated when the message authenticates
+    // Messages may arrive out of order, keys of skipped messages are kept until those messages show up
+    pub fn decrypt(&mut self, message: &[u8]) -> Result<Vec<u8>, EchoError> {
+        self.decrypt_with(message, &mut OsRng)
+    }
+
+    // The generator supplies the key pair of a DH ratchet step, which a message with a new ratchet key starts
+    pub fn decrypt_with<R: RngCore + CryptoRng + ?Sized>(&mut self, message: &[u8], rng: &mut R) -> Result<Vec<u8>, EchoError> {
+        let mut next = self.clone();
+        let plaintext = match next.header_keys {
+            Some(_) => next.apply_encrypted_header_message(message, rng)?,
+            None => next.apply_message(message, rng)?,
+        };
+        *self = next;
+
+        Ok(plaintext)
+    }
+
+    // This function returns how many skipped message keys are currently stored
+    pub fn skipped_message_key_count(&self) -> usize {
+        self.skipped_message_keys.keys.len()
+    }
+
+    // This function returns the current ratchet public key of this side
+    pub fn ratchet_public_key(&self) -> PublicKey {
+        self.dh_self.public_key
+    }
+
+    pub fn has_header_encryption(&self) -> bool {
+        self.header_keys.is_some()
+    }
+
+    fn apply_message<R: RngCore + CryptoRng + ?Sized>(&mut self, message: &[u8], rng: &mut R) -> Result<Vec<u8>, EchoError> {
+        let header = MessageHeader::from_bytes(message)?;
+
+        // A late message of an earlier chain or position uses the key stored when it was skipped
+        if let Some(message_key) = self.skipped_message_keys.take(header.dh.as_bytes(), header.n) {
+            return self.decrypt_with_message_key(&message_key, message, HEADER_LENGTH);
+        }
+
+        if self.dh_remote != Some(header.dh) {
+            self.skip_message_keys(header.pn)?;
+            self.dh_ratchet(&header, rng)?;
+        }
+
+        self.receive(&header, message, HEADER_LENGTH)
+    }
+
+    // RatchetDecryptHE, the header is opened with the header keys of skipped chains, then HKr and then NHKr
+    // Only a header that opens with NHKr starts a DH ratchet step
+    fn apply_encrypted_header_message<R: RngCore + CryptoRng + ?Sized>(&mut self, message: &[u8], rng: &mut R) -> Result<Vec<u8>, EchoError> {
+        if message.len() < ENCRYPTED_HEADER_LENGTH {
+            return Err(EchoError::MalformedMessageHeader);
+        }
+        let encrypted_header = &message[..ENCRYPTED_HEADER_LENGTH];
+
+        // TrySkippedMessageKeysHE
+        let skipped = self
+            .skipped_message_keys
+            .chains()
+            .into_iter()
+            .find_map(|header_key| Some((Zeroizing::new(*header_key), decrypt_header(header_key, encrypted_header, &self.associated_data)?)));
+        if let Some((header_key, header)) = skipped
+            && let Some(message_key) = self.skipped_message_keys.take(&header_key, header.n)
+        {
+            return self.decrypt_with_message_key(&message_key, message, ENCRYPTED_HEADER_LENGTH);
+        }
+
+        // DecryptHeader
+        let header_keys = self.header_keys.as_ref().ok_or(EchoError::SessionNotReady)?;
+        let current = header_keys
+            .receiving
+            .as_ref()
+            .and_then(|header_key| decrypt_header(header_key, encrypted_header, &self.associated_data));
+        let header = match current {
+            Some(header) => header,
+            None => {
+                let header = decrypt_header(&header_keys.next_receiving, encrypted_header, &self.associated_data)
+                    .ok_or(EchoError::DecryptionFailed)?;
+                self.skip_message_keys(header.pn)?;
+                self.dh_ratchet(&header, rng)?;
+                header
+            }
+        };
+
+        self.receive(&header, message, ENCRYPTED_HEADER_LENGTH)
+    }
+
+    // This function decrypts a message of the current receiving chain, storing the keys of the messages it skips
+    fn receive(&mut self, header: &MessageHeader, message: &[u8], header_length: usize) -> Result<Vec<u8>, EchoError> {
+        self.skip_message_keys(header.n)?;
+
+        if header.n < self.receiving_message_number {
+            return Err(EchoError::DuplicateMessage);
+        }
+
+        let chain_key = self.receiving_chain_key.as_ref().ok_or(EchoError::SessionNotReady)?;
+        let (next_chain_key, message_key) = kdf_ck(chain_key);
+        self.receiving_chain_key = Some(next_chain_key);
+        self.receiving_message_number += 1;
+
+        self.decrypt_with_message_key(&message_key, message, header_length)
+    }
+
+    // SkipMessageKeys, stores the keys of the current receiving chain up to (not including) the given message number
+    // The keys are filed under the ratchet public key of the chain, or under HKr with header encryption
+    fn skip_message_keys(&mut self, until: u32) -> Result<(), EchoError> {
+        if until > self.receiving_message_number.saturating_add(MAX_SKIP) {
+            return Err(EchoError::TooManySkippedMessages);
+        }
+
+        let chain = match &self.header_keys {
+            Some(header_keys) => header_keys.receiving.as_deref().copied(),
+            None => self.dh_remote.map(|dh_remote| dh_remote.to_bytes()),
+        };
+
+        if let (Some(mut chain_key), Some(chain)) = (self.receiving_chain_key.take(), chain) {
+            while self.receiving_message_number < until {
+                let (next_chain_key, message_key) = kdf_ck(&chain_key);
+                self.skipped_message_keys.insert(chain, self.receiving_message_number, message_key);
+                chain_key = next_chain_key;
+                self.receiving_message_number += 1;
+            }
+            self.receiving_chain_key = Some(chain_key);
+        }
+
+        Ok(())
+    }
+
+    fn decrypt_with_message_key(&self, message_key: &[u8; 32], message: &[u8], header_length: usize) -> Result<Vec<u8>, EchoError> {
+        let (key, nonce) = message_key_material(message_key);
+        aes_gcm_decrypt(&message[header_length..], key.as_ref(), &nonce, &self.header_aad(&message[..header_length]))
+    }
+
+    // DHRatchet, derives a new receiving chain from their new ratchet key and then a new sending chain from a fresh key pair
+    // With header encryption the next header keys become the current ones and new next header keys come out of KDF_RK
+    fn dh_ratchet<R: RngCore + CryptoRng + ?Sized>(&mut self, header: &MessageHeader, rng: &mut R) -> Result<(), EchoError> {
+        self.previous_chain_length = self.sending_message_number;
+        self.sending_message_number = 0;
+        self.receiving_message_number = 0;
+        self.dh_remote = Some(header.dh);
+
+        let dh_out = self.dh_self.private_key.diffie_hellman(&header.dh)?;
+        let (root_key, receiving_chain_key, next_receiving_header_key) = kdf_rk(&self.root_key, &dh_out);
+        self.root_key = root_key;
+        self.receiving_chain_key = Some(receiving_chain_key);
+
+        self.dh_self = KeyPair::generate_with(rng)?;
+
+        let dh_out = self.dh_self.private_key.diffie_hellman(&header.dh)?;
+        let (root_key, sending_chain_key, next_sending_header_key) = kdf_rk(&self.root_key, &dh_out);
+        self.root_key = root_key;
+        self.sending_chain_key = Some(sending_chain_key);
+
+        if let Some(header_keys) = &mut self.header_keys {
+            header_keys.sending = Some(mem::replace(&mut header_keys.next_sending, next_sending_header_key));
+            header_keys.receiving = Some(mem::replace(&mut header_keys.next_receiving, next_receiving_header_key));
+        }
+
+        Ok(())
+    }
+
+    // CONCAT(AD, header), the X3DH associated data followed by the encoded (or encrypted) header
+    fn header_aad(&self, header_bytes: &[u8]) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(self.associated_data.len() + header_bytes.len());
+        aad.extend_from_slice(&self.associated_data);
+        aad.extend_from_slice(header_bytes);
+        aad
+    }
+
+    // Serialized state (all integers big endian), wrapped by session::SessionRecord:
+    // DHS_PRIVATE (32 bytes) || DHR (flag + 32 bytes) || RK (32 bytes) || CKS (flag + 32 bytes) || CKR (flag + 32 bytes)
+    // || NS (4 bytes) || NR (4 bytes) || PN (4 bytes)
+    // || SKIPPED_COUNT (4 bytes) || (CHAIN (32 bytes, DH or HK) || N (4 bytes) || MK (32 bytes)) * SKIPPED_COUNT, oldest first
+    // || AD_LENGTH (4 bytes) || AD
+    // || HEADER_ENCRYPTION (1 byte, 0 or 1) [|| HKS (flag + 32 bytes) || HKR (flag + 32 bytes) || NHKS (32 bytes) || NHKR (32 bytes)]
+    // The caller sizes the buffer with encoded_len, so it is never reallocated and no unwiped copy of the keys is freed
+    pub(crate) fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(self.dh_self.private_key.as_bytes());
+        encode_optional(bytes, self.dh_remote.as_ref().map(PublicKey::as_bytes));
+        bytes.extend_from_slice(self.root_key.as_ref());
+        encode_optional(bytes, self.sending_chain_key.as_deref());
+        encode_optional(bytes, self.receiving_chain_key.as_deref());
+        bytes.extend_from_slice(&self.sending_message_number.to_be_bytes());
+        bytes.extend_from_slice(&self.receiving_message_number.to_be_bytes());
+        bytes.extend_from_slice(&self.previous_chain_length.to_be_bytes());
+
+        bytes.extend_from_slice(&(self.skipped_message_keys.keys.len() as u32).to_be_bytes());
+        for skipped in &self.skipped_message_keys.keys {
+            bytes.extend_from_slice(&skipped.chain);
+            bytes.extend_from_slice(&skipped.message_number.to_be_bytes());
+            bytes.extend_from_slice(&skipped.message_key);
+        }
+
+        bytes.extend_from_slice(&(self.associated_data.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&self.associated_data);
+
+        match &self.header_keys {
+            Some(header_keys) => {
+                bytes.push(1);
+                encode_optional(bytes, header_keys.sending.as_deref());
+                encode_optional(bytes, header_keys.receiving.as_deref());
+                bytes.extend_from_slice(header_keys.next_sending.as_ref());
+                bytes.extend_from_slice(header_keys.next_receiving.as_ref());
+            }
+            None => bytes.push(0),
+        }
+    }
+
+    // This function returns the exact number of bytes encode writes
+    pub(crate) fn encoded_len(&self) -> usize {
+        let header_keys = match &self.header_keys {
+            Some(header_keys) => optional_len(header_keys.sending.is_some()) + optional_len(header_keys.receiving.is_some()) + 64,
+            None => 0,
+        };
+
+        32 + optional_len(self.dh_remote.is_some())
+            + 32
+            + optional_len(self.sending_chain_key.is_some())
+            + optional_len(self.receiving_chain_key.is_some())
+            + 12
+            + 4
+            + self.skipped_message_keys.keys.len() * (32 + 4 + 32)
+            + 4
+            + self.associated_data.len()
+            + 1
+            + header_keys
+    }
+
+    pub(crate) fn decode(reader: &mut Reader) -> Result<RatchetSession, EchoError> {
+        let dh_self = reader.key_pair()?;
+        let dh_remote = decode_optional(reader)?.map(PublicKey::from);
+        let root_key = Zeroizing::new(reader.array()?);
+        let sending_chain_key = decode_optional(reader)?.map(Zeroizing::new);
+        let receiving_chain_key = decode_optional(reader)?.map(Zeroizing::new);
+        let sending_message_number = reader.u32()?;
+        let receiving_message_number = reader.u32()?;
+        let previous_chain_length = reader.u32()?;
+
+        let skipped_count = reader.u32()? as usize;
+        if skipped_count > MAX_SKIPPED_KEYS {
+            return Err(EchoError::MalformedSessionRecord);
+        }
+
+        let mut skipped_message_keys = SkippedMessageKeys::default();
+        for _ in 0..skipped_count {
+            let chain = reader.array()?;
+            let message_number = reader.u32()?;
+            let message_key = Zeroizing::new(reader.array()?);
+
+            if skipped_message_keys.contains(&chain, message_number) {
+                return Err(EchoError::MalformedSessionRecord);
+            }
+            skipped_message_keys.insert(chain, message_number, message_key);
+        }
+
+        let associated_data_length = reader.u32()? as usize;
+        let associated_data = reader.take(associated_data_length)?.to_vec();
+
+        let header_keys = match reader.u8()? {
+            0 => None,
+            1 => Some(HeaderKeys {
+                sending: decode_optional(reader)?.map(Zeroizing::new),
+                receiving: decode_optional(reader)?.map(Zeroizing::new),
+                next_sending: Zeroizing::new(reader.array()?),
+                next_receiving: Zeroizing::new(reader.array()?),
+            }),
+            _ => return Err(EchoError::MalformedSessionRecord),
+        };
+
+        Ok(RatchetSession {
+            dh_self,
+            dh_remote,
+            root_key,
+            sending_chain_key,
+            receiving_chain_key,
+            sending_message_number,
+            receiving_message_number,
+            previous_chain_length,
+            skipped_message_keys,
+            associated_data,
+            header_keys,
+        })
+    }
+}
+
+// Length of an encode_optional field, the flag and the 32 byte value when present
+fn optional_len(present: bool) -> usize {
+    if present { 33 } else { 1 }
+}
+
+fn encode_optional(bytes: &mut Vec<u8>, value: Option<&[u8; 32]>) {
+    match value {
+        Some(value) => {
+            bytes.push(1);
+            bytes.extend_from_slice(value);
+        }
+        None => bytes.push(0),
+    }
+}
+
+fn decode_optional(reader: &mut Reader) -> Result<Option<[u8; 32]>, EchoError> {
+    match reader.u8()? {
+        0 => Ok(None),
+        1 => Ok(Some(reader.array()?)),
+        _ => Err(EchoError::MalformedSessionRecord),
+    }
+}
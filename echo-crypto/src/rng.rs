@@ -0,0 +1,12 @@
+use crate::error::EchoError;
+
+// Randomness used by key generation
+// OsRng is backed by getrandom: the operating system generator natively and crypto.getRandomValues in wasm
+// Every key generator also has a _with variant that takes any CryptoRng, so tests can inject a seeded generator
+// AES-GCM nonces (envelopes and encrypted headers) are always drawn from OsRng
+pub use rand_core::{CryptoRng, OsRng, RngCore};
+
+// This function fills the buffer from the generator, a failing generator is reported as RandomnessUnavailable
+pub(crate) fn fill<R: RngCore + CryptoRng + ?Sized>(rng: &mut R, bytes: &mut [u8]) -> Result<(), EchoError> {
+    rng.try_fill_bytes(bytes).map_err(|_| EchoError::RandomnessUnavailable)
+}
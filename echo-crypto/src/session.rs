@@ -0,0 +1,92 @@
+use crate::codec::Reader;
+use crate::error::EchoError;
+use crate::keys::PublicKey;
+use crate::ratchet::RatchetSession;
+use zeroize::Zeroizing;
+
+// Record serialization layout:
+// VERSION (1 byte) || REMOTE_IK (32 bytes) || RATCHET_STATE (see RatchetSession::encode)
+// The encoding is deterministic, the same state always serializes to the same bytes
+// Version 2 added the header encryption keys at the end of the ratchet state
+pub const SESSION_RECORD_VERSION: u8 = 2;
+
+// An upgrade step from an older record body, MIGRATIONS[v - 1] turns a version v body into a version v + 1 body
+// When the layout changes: bump SESSION_RECORD_VERSION and append the step that converts the previous layout
+type Migration = fn(&[u8]) -> Result<Zeroizing<Vec<u8>>, EchoError>;
+const MIGRATIONS: &[Migration] = &[add_header_encryption_flag];
+
+// Version 1 to 2, sessions saved before header encryption existed do not use it
+fn add_header_encryption_flag(body: &[u8]) -> Result<Zeroizing<Vec<u8>>, EchoError> {
+    let mut upgraded = Zeroizing::new(Vec::with_capacity(body.len() + 1));
+    upgraded.extend_from_slice(body);
+    upgraded.push(0);
+    Ok(upgraded)
+}
+
+// This struct is everything stored for one conversation: the Double Ratchet state and the peer's identity key
+// A record is always written and read as a single blob, so a conversation can never be half saved or half loaded
+#[derive(Clone)]
+pub struct SessionRecord {
+    remote_identity: PublicKey,
+    session: RatchetSession,
+}
+
+impl SessionRecord {
+    pub fn new(remote_identity: PublicKey, session: RatchetSession) -> SessionRecord {
+        SessionRecord { remote_identity, session }
+    }
+
+    pub fn remote_identity(&self) -> &PublicKey {
+        &self.remote_identity
+    }
+
+    pub fn session(&self) -> &RatchetSession {
+        &self.session
+    }
+
+    // This function encrypts the next message, see RatchetSession::encrypt
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, EchoError> {
+        self.session.encrypt(plaintext)
+    }
+
+    // This function decrypts a message, the record is left untouched when the message does not authenticate
+    pub fn decrypt(&mut self, message: &[u8]) -> Result<Vec<u8>, EchoError> {
+        self.session.decrypt(message)
+    }
+
+    // This function serializes the record at the current version
+    // The buffer is allocated at its final size, growing it would free copies of the ratchet keys without wiping them
+    pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let mut bytes = Zeroizing::new(Vec::with_capacity(1 + 32 + self.session.encoded_len()));
+        let capacity = bytes.capacity();
+
+        bytes.push(SESSION_RECORD_VERSION);
+        bytes.extend_from_slice(self.remote_identity.as_bytes());
+        self.session.encode(&mut bytes);
+
+        debug_assert_eq!(bytes.capacity(), capacity);
+        debug_assert_eq!(bytes.len(), 1 + 32 + self.session.encoded_len());
+        bytes
+    }
+
+    // This function parses a record of the current or any older version, older bodies are migrated first
+    // Records written by a newer version are rejected with UnsupportedVersion
+    pub fn from_bytes(bytes: &[u8]) -> Result<SessionRecord, EchoError> {
+        let (&version, body) = bytes.split_first().ok_or(EchoError::MalformedSessionRecord)?;
+        if version == 0 || version > SESSION_RECORD_VERSION {
+            return Err(EchoError::UnsupportedVersion);
+        }
+
+        let mut body = Zeroizing::new(body.to_vec());
+        for migration in &MIGRATIONS[version as usize - 1..] {
+            body = migration(&body)?;
+        }
+
+        let mut reader = Reader::new(&body, EchoError::MalformedSessionRecord);
+        let remote_identity = PublicKey::from(reader.array()?);
+        let session = RatchetSession::decode(&mut reader)?;
+        reader.finish()?;
+
+        Ok(SessionRecord { remote_identity, session })
+    }
+}
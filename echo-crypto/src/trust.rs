@@ -0,0 +1,122 @@
+use crate::codec::Reader;
+use crate::error::EchoError;
+use crate::keys::PublicKey;
+use std::collections::BTreeMap;
+
+// Trust store serialization layout (all integers big endian):
+// VERSION (1 byte) || COUNT (4 bytes) || (ID_LENGTH (4 bytes) || ID (UTF-8) || IK (32 bytes)) * COUNT
+pub const TRUST_STORE_VERSION: u8 = 1;
+
+// Result of comparing a fetched identity key with the one remembered for the contact
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TrustStatus {
+    // No key has been seen for this contact yet
+    Unknown,
+    // The key is the one seen (or approved) before
+    Trusted,
+    // The contact had a different key, this is either a reinstall or a man in the middle
+    Changed,
+}
+
+// This struct is proof that the trust store accepted an identity key for a contact
+// It can only be built by TrustStore::ensure_trusted or TrustStore::approve, X3DH only runs against such a key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrustedIdentity {
+    identity_key: PublicKey,
+    status: TrustStatus,
+}
+
+impl TrustedIdentity {
+    pub fn identity_key(&self) -> &PublicKey {
+        &self.identity_key
+    }
+
+    // Unknown when the key was seen for the first time, Trusted when it was remembered or approved before
+    pub fn status(&self) -> TrustStatus {
+        self.status
+    }
+}
+
+// This struct remembers the identity key of every contact the first time it is seen (trust on first use)
+// A later different key is never accepted silently, the user has to approve it first
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrustStore {
+    identities: BTreeMap<String, PublicKey>,
+}
+
+impl TrustStore {
+    pub fn new() -> TrustStore {
+        TrustStore::default()
+    }
+
+    // This function compares a fetched identity key with the remembered one without changing the store
+    pub fn check(&self, contact_id: &str, identity_key: &PublicKey) -> TrustStatus {
+        match self.identities.get(contact_id) {
+            None => TrustStatus::Unknown,
+            Some(known) if known == identity_key => TrustStatus::Trusted,
+            Some(_) => TrustStatus::Changed,
+        }
+    }
+
+    // This function must be called before building a session with a fetched identity key, X3DH needs the returned token
+    // An unknown key is remembered and accepted, a changed key fails with IdentityKeyChanged until approve is called
+    pub fn ensure_trusted(&mut self, contact_id: &str, identity_key: &PublicKey) -> Result<TrustedIdentity, EchoError> {
+        let status = self.check(contact_id, identity_key);
+        match status {
+            TrustStatus::Unknown => {
+                self.identities.insert(contact_id.to_owned(), *identity_key);
+            }
+            TrustStatus::Trusted => {}
+            TrustStatus::Changed => return Err(EchoError::IdentityKeyChanged),
+        }
+
+        Ok(TrustedIdentity { identity_key: *identity_key, status })
+    }
+
+    // This function records that the user explicitly accepted a new identity key for the contact
+    pub fn approve(&mut self, contact_id: &str, identity_key: &PublicKey) -> TrustedIdentity {
+        self.identities.insert(contact_id.to_owned(), *identity_key);
+        TrustedIdentity { identity_key: *identity_key, status: TrustStatus::Trusted }
+    }
+
+    pub fn identity_key(&self, contact_id: &str) -> Option<&PublicKey> {
+        self.identities.get(contact_id)
+    }
+
+    pub fn forget(&mut self, contact_id: &str) -> bool {
+        self.identities.remove(contact_id).is_some()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![TRUST_STORE_VERSION];
+        bytes.extend_from_slice(&(self.identities.len() as u32).to_be_bytes());
+        for (contact_id, identity_key) in &self.identities {
+            bytes.extend_from_slice(&(contact_id.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(contact_id.as_bytes());
+            bytes.extend_from_slice(identity_key.as_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<TrustStore, EchoError> {
+        let mut reader = Reader::new(bytes, EchoError::MalformedTrustStore);
+
+        if reader.u8()? != TRUST_STORE_VERSION {
+            return Err(EchoError::UnsupportedVersion);
+        }
+
+        let mut identities = BTreeMap::new();
+        for _ in 0..reader.u32()? {
+            let id_length = reader.u32()? as usize;
+            let contact_id = std::str::from_utf8(reader.take(id_length)?).map_err(|_| EchoError::MalformedTrustStore)?;
+            let identity_key = PublicKey::from(reader.array()?);
+
+            if identities.insert(contact_id.to_owned(), identity_key).is_some() {
+                return Err(EchoError::MalformedTrustStore);
+            }
+        }
+
+        reader.finish()?;
+        Ok(TrustStore { identities })
+    }
+}
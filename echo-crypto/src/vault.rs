@@ -0,0 +1,299 @@
+use crate::aead;
+use crate::codec::Reader;
+use crate::error::EchoError;
+use crate::keystore::KeyStore;
+use crate::rng::{self, CryptoRng, OsRng, RngCore};
+use crate::session::SessionRecord;
+use crate::trust::TrustStore;
+use argon2::{Algorithm, Argon2, Params, Version};
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+// Vault serialization layout (all integers big endian):
+// VERSION (1 byte) || M_COST (4 bytes, KiB) || T_COST (4 bytes) || P_COST (4 bytes) || SALT (16 bytes)
+// || WRAPPED_DEK (61 bytes, aead envelope of the DEK under the KEK) || PAYLOAD (aead envelope of the contents under the DEK)
+// The KEK is Argon2id(password, SALT) and never stored, the DEK is random and only changes when a new vault is created
+// Version 2 added the trust store to the contents, version 1 vaults are upgraded when they are unlocked
+pub const VAULT_VERSION: u8 = 2;
+pub const SALT_LENGTH: usize = 16;
+const WRAPPED_DEK_LENGTH: usize = 1 + aead::NONCE_LENGTH + aead::KEY_LENGTH + aead::TAG_LENGTH;
+const KDF_HEADER_LENGTH: usize = 1 + 4 + 4 + 4 + SALT_LENGTH;
+
+// Upper bounds on the Argon2 costs accepted from a stored vault, the header is read before anything is authenticated
+// so a tampered header must not be able to hang the tab or ask for more memory than a browser tab can allocate
+pub const MAX_MEMORY_KIB: u32 = 256 * 1024;
+pub const MAX_ITERATIONS: u32 = 10;
+pub const MAX_PARALLELISM: u32 = 4;
+
+// Associated data of the payload envelope, the DEK envelope is bound to the KDF header instead
+const PAYLOAD_AAD: &[u8] = b"EchoVaultPayload";
+
+// This struct holds the Argon2id cost parameters, they are stored in the vault header next to the salt
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Argon2Params {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+// OWASP recommendation for Argon2id: 19 MiB, 2 iterations, 1 lane
+impl Default for Argon2Params {
+    fn default() -> Argon2Params {
+        Argon2Params {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl Argon2Params {
+    fn argon2(&self) -> Result<Argon2<'static>, EchoError> {
+        if self.memory_kib > MAX_MEMORY_KIB || self.iterations > MAX_ITERATIONS || self.parallelism > MAX_PARALLELISM {
+            return Err(EchoError::InvalidKdfParams);
+        }
+
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(aead::KEY_LENGTH))
+            .map_err(|_| EchoError::InvalidKdfParams)?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+// This function derives the key-encryption key from the password
+fn derive_kek(password: &[u8], params: &Argon2Params, salt: &[u8; SALT_LENGTH]) -> Result<Zeroizing<[u8; 32]>, EchoError> {
+    let mut kek = Zeroizing::new([0u8; 32]);
+    params
+        .argon2()?
+        .hash_password_into(password, salt, kek.as_mut())
+        .map_err(|_| EchoError::InvalidKdfParams)?;
+    Ok(kek)
+}
+
+fn kdf_header(version: u8, params: &Argon2Params, salt: &[u8; SALT_LENGTH]) -> [u8; KDF_HEADER_LENGTH] {
+    let mut header = [0u8; KDF_HEADER_LENGTH];
+    header[0] = version;
+    header[1..5].copy_from_slice(&params.memory_kib.to_be_bytes());
+    header[5..9].copy_from_slice(&params.iterations.to_be_bytes());
+    header[9..13].copy_from_slice(&params.parallelism.to_be_bytes());
+    header[13..].copy_from_slice(salt);
+    header
+}
+
+fn random_salt<R: RngCore + CryptoRng + ?Sized>(rng: &mut R) -> Result<[u8; SALT_LENGTH], EchoError> {
+    let mut salt = [0u8; SALT_LENGTH];
+    rng::fill(rng, &mut salt)?;
+    Ok(salt)
+}
+
+// This struct is an unlocked password protected store: the key store of the user, the identity keys of their contacts
+// and their serialized ratchet sessions
+// It keeps the DEK in memory so saving does not rerun Argon2, only unlock and change_password do
+pub struct Vault {
+    params: Argon2Params,
+    salt: [u8; SALT_LENGTH],
+    wrapped_dek: Vec<u8>,
+    dek: Zeroizing<[u8; 32]>,
+    pub key_store: KeyStore,
+    pub trust_store: TrustStore,
+    sessions: BTreeMap<String, Zeroizing<Vec<u8>>>,
+}
+
+impl Vault {
+    // This function creates a vault with a fresh DEK wrapped under a key derived from the password
+    pub fn create(password: &[u8], params: Argon2Params, key_store: KeyStore) -> Result<Vault, EchoError> {
+        Vault::create_with(password, params, key_store, &mut OsRng)
+    }
+
+    // The generator supplies the DEK and the salt
+    pub fn create_with<R: RngCore + CryptoRng + ?Sized>(
+        password: &[u8],
+        params: Argon2Params,
+        key_store: KeyStore,
+        rng: &mut R,
+    ) -> Result<Vault, EchoError> {
+        let dek = aead::generate_key_with(rng)?;
+
+        let mut vault = Vault {
+            params,
+            salt: [0u8; SALT_LENGTH],
+            wrapped_dek: Vec::new(),
+            dek,
+            key_store,
+            trust_store: TrustStore::new(),
+            sessions: BTreeMap::new(),
+        };
+        vault.wrap_dek(password, params, rng)?;
+        Ok(vault)
+    }
+
+    // This function decrypts a vault written by to_bytes
+    // A wrong password fails with WrongPassword, a damaged payload with DecryptionFailed
+    pub fn unlock(bytes: &[u8], password: &[u8]) -> Result<Vault, EchoError> {
+        let mut reader = Reader::new(bytes, EchoError::MalformedVault);
+
+        let version = reader.u8()?;
+        if version == 0 || version > VAULT_VERSION {
+            return Err(EchoError::UnsupportedVersion);
+        }
+
+        let params = Argon2Params {
+            memory_kib: reader.u32()?,
+            iterations: reader.u32()?,
+            parallelism: reader.u32()?,
+        };
+        let salt = reader.array()?;
+        let wrapped_dek = reader.take(WRAPPED_DEK_LENGTH)?.to_vec();
+        let payload = reader.rest();
+
+        let kek = derive_kek(password, &params, &salt)?;
+        let unwrapped = Zeroizing::new(
+            aead::open_envelope(&wrapped_dek, kek.as_ref(), &kdf_header(version, &params, &salt))
+                .map_err(|_| EchoError::WrongPassword)?,
+        );
+        let dek: Zeroizing<[u8; 32]> = Zeroizing::new(unwrapped.as_slice().try_into().map_err(|_| EchoError::MalformedVault)?);
+
+        let contents = Zeroizing::new(aead::open_envelope(payload, dek.as_ref(), PAYLOAD_AAD)?);
+        let (key_store, trust_store, sessions) = decode_contents(version, &contents)?;
+
+        let mut vault = Vault { params, salt, wrapped_dek, dek, key_store, trust_store, sessions };
+
+        // The wrapped DEK is bound to the header version, re-wrap it so the vault is saved at the current version
+        if version < VAULT_VERSION {
+            vault.wrap_dek(password, params, &mut OsRng)?;
+        }
+
+        Ok(vault)
+    }
+
+    // This function encrypts the current contents under the DEK and prepends the KDF header and wrapped DEK
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EchoError> {
+        let payload = aead::seal_envelope(&self.encode_contents(), self.dek.as_ref(), PAYLOAD_AAD)?;
+
+        let mut bytes = Vec::with_capacity(KDF_HEADER_LENGTH + WRAPPED_DEK_LENGTH + payload.len());
+        bytes.extend_from_slice(&kdf_header(VAULT_VERSION, &self.params, &self.salt));
+        bytes.extend_from_slice(&self.wrapped_dek);
+        bytes.extend_from_slice(&payload);
+        Ok(bytes)
+    }
+
+    // This function re-wraps the DEK under a key derived from the new password with a fresh salt
+    // The contents are not re-encrypted, the current password is checked against the stored wrapped DEK first
+    pub fn change_password(&mut self, current_password: &[u8], new_password: &[u8], params: Argon2Params) -> Result<(), EchoError> {
+        self.change_password_with(current_password, new_password, params, &mut OsRng)
+    }
+
+    pub fn change_password_with<R: RngCore + CryptoRng + ?Sized>(
+        &mut self,
+        current_password: &[u8],
+        new_password: &[u8],
+        params: Argon2Params,
+        rng: &mut R,
+    ) -> Result<(), EchoError> {
+        let kek = derive_kek(current_password, &self.params, &self.salt)?;
+        aead::open_envelope(&self.wrapped_dek, kek.as_ref(), &kdf_header(VAULT_VERSION, &self.params, &self.salt))
+            .map(Zeroizing::new)
+            .map_err(|_| EchoError::WrongPassword)?;
+
+        self.wrap_dek(new_password, params, rng)
+    }
+
+    pub fn params(&self) -> Argon2Params {
+        self.params
+    }
+
+    // This function returns the session stored under the ID, as written by SessionRecord::to_bytes
+    pub fn session(&self, id: &str) -> Option<&[u8]> {
+        self.sessions.get(id).map(|record| record.as_slice())
+    }
+
+    pub fn set_session(&mut self, id: &str, record: Vec<u8>) {
+        self.sessions.insert(id.to_owned(), Zeroizing::new(record));
+    }
+
+    // This function decodes the record stored under the ID, records of older versions are migrated
+    pub fn session_record(&self, id: &str) -> Result<Option<SessionRecord>, EchoError> {
+        self.session(id).map(SessionRecord::from_bytes).transpose()
+    }
+
+    // This function stores a record under the ID, the serialized ratchet state only ever exists inside the vault
+    pub fn set_session_record(&mut self, id: &str, record: &SessionRecord) {
+        self.set_session(id, record.to_bytes().to_vec());
+    }
+
+    pub fn remove_session(&mut self, id: &str) -> bool {
+        self.sessions.remove(id).is_some()
+    }
+
+    pub fn session_ids(&self) -> impl Iterator<Item = &str> {
+        self.sessions.keys().map(String::as_str)
+    }
+
+    fn wrap_dek<R: RngCore + CryptoRng + ?Sized>(&mut self, password: &[u8], params: Argon2Params, rng: &mut R) -> Result<(), EchoError> {
+        let salt = random_salt(rng)?;
+        let kek = derive_kek(password, &params, &salt)?;
+        self.wrapped_dek = aead::seal_envelope(self.dek.as_ref(), kek.as_ref(), &kdf_header(VAULT_VERSION, &params, &salt))?;
+        self.params = params;
+        self.salt = salt;
+        Ok(())
+    }
+
+    // KEYSTORE_LENGTH (4 bytes) || KEYSTORE || SESSION_COUNT (4 bytes)
+    // || (ID_LENGTH (4 bytes) || ID (UTF-8) || RECORD_LENGTH (4 bytes) || RECORD) * SESSION_COUNT
+    // || TRUST_STORE_LENGTH (4 bytes) || TRUST_STORE, since version 2
+    // The buffer is allocated at its final size, growing it would free copies of the key store and the records without wiping them
+    fn encode_contents(&self) -> Zeroizing<Vec<u8>> {
+        let key_store = self.key_store.to_bytes();
+        let trust_store = self.trust_store.to_bytes();
+
+        let sessions_length: usize = self.sessions.iter().map(|(id, record)| 4 + id.len() + 4 + record.len()).sum();
+        let length = 4 + key_store.len() + 4 + sessions_length + 4 + trust_store.len();
+        let mut bytes = Zeroizing::new(Vec::with_capacity(length));
+        let capacity = bytes.capacity();
+
+        bytes.extend_from_slice(&(key_store.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&key_store);
+        bytes.extend_from_slice(&(self.sessions.len() as u32).to_be_bytes());
+        for (id, record) in &self.sessions {
+            bytes.extend_from_slice(&(id.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(id.as_bytes());
+            bytes.extend_from_slice(&(record.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(record);
+        }
+
+        bytes.extend_from_slice(&(trust_store.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&trust_store);
+
+        debug_assert_eq!(bytes.capacity(), capacity);
+        debug_assert_eq!(bytes.len(), length);
+        bytes
+    }
+}
+
+type Sessions = BTreeMap<String, Zeroizing<Vec<u8>>>;
+
+fn decode_contents(version: u8, bytes: &[u8]) -> Result<(KeyStore, TrustStore, Sessions), EchoError> {
+    let mut reader = Reader::new(bytes, EchoError::MalformedVault);
+
+    let key_store_length = reader.u32()? as usize;
+    let key_store = KeyStore::from_bytes(reader.take(key_store_length)?)?;
+
+    let mut sessions = BTreeMap::new();
+    for _ in 0..reader.u32()? {
+        let id_length = reader.u32()? as usize;
+        let id = std::str::from_utf8(reader.take(id_length)?).map_err(|_| EchoError::MalformedVault)?;
+        let record_length = reader.u32()? as usize;
+        let record = reader.take(record_length)?;
+        sessions.insert(id.to_owned(), Zeroizing::new(record.to_vec()));
+    }
+
+    // Version 1 vaults predate the trust store, their contacts start out unknown
+    let trust_store = if version >= 2 {
+        let trust_store_length = reader.u32()? as usize;
+        TrustStore::from_bytes(reader.take(trust_store_length)?)?
+    } else {
+        TrustStore::new()
+    };
+
+    reader.finish()?;
+    Ok((key_store, trust_store, sessions))
+}
@@ -0,0 +1,105 @@
+use crate::bundle::VerifiedPreKeyBundle;
+use crate::error::EchoError;
+use crate::kdf::hkdf_sha256_32;
+use crate::keys::{PrivateKey, PublicKey};
+use crate::trust::TrustedIdentity;
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+// Application specific info string for the X3DH KDF
+const X3DH_INFO: &[u8] = b"EchoProtocol";
+
+// F, 32 0xFF bytes prepended to the DH outputs when using X25519 (domain separation from XEdDSA)
+const X3DH_F: [u8; 32] = [0xFF; 32];
+
+// HKDF salt, a zero filled byte sequence as long as the SHA-256 output
+const X3DH_SALT: [u8; 32] = [0u8; 32];
+
+// This struct is the outcome of X3DH: the shared secret SK and the associated data AD = IK_A || IK_B
+// SK is wiped when the result is dropped
+#[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub struct X3dhResult {
+    pub shared_secret: [u8; 32],
+    pub associated_data: [u8; 64],
+}
+
+// SK is never printed
+impl fmt::Debug for X3dhResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("X3dhResult")
+            .field("shared_secret", &"<redacted>")
+            .field("associated_data", &self.associated_data)
+            .finish()
+    }
+}
+
+// SK = HKDF(F || DH1 || DH2 || DH3 || DH4), DH4 is only present when a one-time prekey was used
+fn derive_result(dh_outputs: &[Zeroizing<[u8; 32]>], initiator_identity: &PublicKey, responder_identity: &PublicKey) -> X3dhResult {
+    let mut input_key_material = Zeroizing::new(Vec::with_capacity(32 * (dh_outputs.len() + 1)));
+    input_key_material.extend_from_slice(&X3DH_F);
+    for dh_output in dh_outputs {
+        input_key_material.extend_from_slice(dh_output.as_ref());
+    }
+
+    let shared_secret = *hkdf_sha256_32(&input_key_material, &X3DH_SALT, X3DH_INFO);
+
+    let mut associated_data = [0u8; 64];
+    associated_data[..32].copy_from_slice(initiator_identity.as_bytes());
+    associated_data[32..].copy_from_slice(responder_identity.as_bytes());
+
+    X3dhResult {
+        shared_secret,
+        associated_data,
+    }
+}
+
+// Initiator side (Alice), only a bundle whose signed prekey signature was verified can be used
+// and its identity key must be the one the trust store accepted, otherwise this fails with UntrustedIdentityKey
+// DH1 = DH(IK_A, SPK_B), DH2 = DH(EK_A, IK_B), DH3 = DH(EK_A, SPK_B), DH4 = DH(EK_A, OPK_B)
+// Any low order key in the bundle fails with LowOrderPublicKey
+pub fn initiate(
+    bundle: &VerifiedPreKeyBundle,
+    their_identity: &TrustedIdentity,
+    identity_private: &PrivateKey,
+    ephemeral_private: &PrivateKey,
+) -> Result<X3dhResult, EchoError> {
+    let bundle = bundle.bundle();
+    if their_identity.identity_key() != &bundle.identity_key {
+        return Err(EchoError::UntrustedIdentityKey);
+    }
+
+    let mut dh_outputs = vec![
+        identity_private.diffie_hellman(&bundle.signed_prekey)?,
+        ephemeral_private.diffie_hellman(&bundle.identity_key)?,
+        ephemeral_private.diffie_hellman(&bundle.signed_prekey)?,
+    ];
+    if let Some((_, one_time_prekey)) = &bundle.one_time_prekey {
+        dh_outputs.push(ephemeral_private.diffie_hellman(one_time_prekey)?);
+    }
+
+    Ok(derive_result(&dh_outputs, &identity_private.public_key(), &bundle.identity_key))
+}
+
+// Responder side (Bob), mirrors the initiator with the private halves of the bundle
+// The initiator's identity key is only taken as a token from the trust store
+// A low order identity or ephemeral key from the initiator fails with LowOrderPublicKey
+// DH1 = DH(SPK_B, IK_A), DH2 = DH(IK_B, EK_A), DH3 = DH(SPK_B, EK_A), DH4 = DH(OPK_B, EK_A)
+pub fn respond(
+    identity_private: &PrivateKey,
+    signed_prekey_private: &PrivateKey,
+    one_time_prekey_private: Option<&PrivateKey>,
+    their_identity: &TrustedIdentity,
+    their_ephemeral_public: &PublicKey,
+) -> Result<X3dhResult, EchoError> {
+    let their_identity_public = their_identity.identity_key();
+    let mut dh_outputs = vec![
+        signed_prekey_private.diffie_hellman(their_identity_public)?,
+        identity_private.diffie_hellman(their_ephemeral_public)?,
+        signed_prekey_private.diffie_hellman(their_ephemeral_public)?,
+    ];
+    if let Some(one_time_prekey_private) = one_time_prekey_private {
+        dh_outputs.push(one_time_prekey_private.diffie_hellman(their_ephemeral_public)?);
+    }
+
+    Ok(derive_result(&dh_outputs, their_identity_public, &identity_private.public_key()))
+}
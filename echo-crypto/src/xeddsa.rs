@@ -0,0 +1,359 @@
+use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::Scalar;
+use sha2::{Digest, Sha512};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::error::EchoError;
+use crate::kdf::sha512_bytes;
+use crate::keys::{clamp, PrivateKey, PublicKey};
+
+// hash1 domain separation prefix from the XEdDSA spec: 2^256 - 1 - 1 encoded as 32 little endian bytes
+const HASH1_PREFIX: [u8; 32] = {
+    let mut prefix = [0xFFu8; 32];
+    prefix[0] = 0xFE;
+    prefix
+};
+
+// p = 2^255 - 19 as 32 little endian bytes
+const FIELD_PRIME: [u8; 32] = {
+    let mut p = [0xFFu8; 32];
+    p[0] = 0xED;
+    p[31] = 0x7F;
+    p
+};
+
+// This struct is used to hold the decoded signature components
+pub struct DecodedXedSignature {
+    pub r: EdwardsPoint,
+    pub s: Scalar,
+    pub r_bytes: [u8; 32],
+}
+
+// This function decodes a signature from a byte array
+pub fn decode_xeddsa_signature(signature: &[u8]) -> Result<DecodedXedSignature, EchoError> {
+    // Check if signature length is 64 bytes
+    if signature.len() != 64 {
+        return Err(EchoError::InvalidSignature);
+    }
+
+    let mut s_bytes = [0u8; 32];
+    let mut r_bytes = [0u8; 32];
+
+    // Extract R and S from the signature
+    s_bytes.copy_from_slice(&signature[32..64]);
+    r_bytes.copy_from_slice(&signature[0..32]);
+
+    // Decode R point (Nonce point R)
+    let compressed_r = CompressedEdwardsY(r_bytes);
+    let r_point = compressed_r
+        .decompress()
+        .ok_or(EchoError::InvalidSignature)?;
+
+    let s_ctopt = Scalar::from_canonical_bytes(s_bytes);
+    if s_ctopt.is_some().unwrap_u8() == 0 {
+        return Err(EchoError::InvalidSignature);
+    }
+    let s_scalar = s_ctopt.unwrap();
+
+    // Decoded R, S from Signature
+    Ok(DecodedXedSignature {
+        r: r_point,
+        s: s_scalar,
+        r_bytes,
+    })
+}
+
+// This function reduces a 64 byte hash to a scalar mod L (% L)
+// The reduction runs in constant time, so it is safe on hashes of secret input
+pub fn reduce_hash_mod_l(hash: &[u8; 64]) -> Scalar {
+    Scalar::from_bytes_mod_order_wide(hash)
+}
+
+// This function finalizes a hash over secret input into a buffer that is wiped when dropped
+fn finalize_secret(hasher: Sha512) -> Zeroizing<[u8; 64]> {
+    let mut digest = hasher.finalize();
+    let secret = Zeroizing::new(digest.into());
+    digest.zeroize();
+    secret
+}
+
+// This function finalizes a hash over public input and reduces it into a scalar mod L
+fn hash_to_scalar(hasher: Sha512) -> Scalar {
+    reduce_hash_mod_l(&hasher.finalize().into())
+}
+
+// This function finalizes a hash over secret input and reduces it into a scalar mod L, both are wiped when dropped
+fn secret_hash_to_scalar(hasher: Sha512) -> Zeroizing<Scalar> {
+    Zeroizing::new(reduce_hash_mod_l(&finalize_secret(hasher)))
+}
+
+// This function expands a private key into the Ed25519 style scalar a (clamped) and the Prefix
+// Returns a || Prefix
+// Both halves are secret, the returned value is wiped when dropped
+pub fn expand_private_key(private_key_bytes: &[u8]) -> Zeroizing<[u8; 64]> {
+    //Sha512 the private key
+    let mut expanded = Zeroizing::new(sha512_bytes(private_key_bytes));
+
+    // Clamp the first half, the second half is the prefix
+    let mut a = Zeroizing::new([0u8; 32]);
+    a.copy_from_slice(&expanded[0..32]);
+    clamp(&mut a);
+    expanded[0..32].copy_from_slice(a.as_ref());
+
+    expanded
+}
+
+// Compute r, r = SHA(Prefix + message) % L
+// where Prefix is the prefix from the expanded key and message is the message to sign
+pub fn deterministic_nonce(prefix: &[u8], message: &[u8]) -> Zeroizing<[u8; 32]> {
+    let mut hasher = Sha512::new();
+    hasher.update(prefix);
+    hasher.update(message);
+
+    Zeroizing::new(secret_hash_to_scalar(hasher).to_bytes())
+}
+
+// Compute R, R = B * r
+// where B is the base point and r is the nonce
+pub fn nonce_point(nonce: &[u8; 32]) -> [u8; 32] {
+    let scalar = Scalar::from_bytes_mod_order(*nonce);
+    (scalar * ED25519_BASEPOINT_POINT).compress().to_bytes()
+}
+
+// Compute k, k = SHA(R || publicEdKey || message) % L
+// where R is the nonce point, publicEdKey is the public key, and message is the message to sign
+pub fn challenge_hash(nonce_point: &[u8; 32], public_ed_key: &[u8; 32], message: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha512::new();
+    hasher.update(nonce_point);
+    hasher.update(public_ed_key);
+    hasher.update(message);
+
+    hash_to_scalar(hasher).to_bytes()
+}
+
+// Compute s, s = r + k * a
+// where r is the nonce, k is the challenge hash, and a is the private key scalar
+pub fn signature_scalar(nonce: &[u8; 32], challenge_hash: &[u8; 32], ed_private_scalar: &[u8; 32]) -> [u8; 32] {
+    let r_scalar = Zeroizing::new(Scalar::from_bytes_mod_order(*nonce));
+    let k_scalar = Scalar::from_bytes_mod_order(*challenge_hash);
+    let a_scalar = Zeroizing::new(Scalar::from_bytes_mod_order(*ed_private_scalar));
+
+    (*r_scalar + k_scalar * *a_scalar).to_bytes()
+}
+
+// Verify an Ed25519 signature against an Edwards public key
+// Returns true if the signature is valid, false otherwise
+pub fn verify_signature(signature: &[u8], message: &[u8], public_ed_key: &[u8]) -> bool {
+    if signature.len() != 64 || public_ed_key.len() != 32 {
+        return false;
+    }
+
+    // Try to decode signature
+    let decoded_signature = match decode_xeddsa_signature(signature) {
+        Ok(sig) => sig,
+        Err(_) => return false,
+    };
+    let r = decoded_signature.r;
+    let s = decoded_signature.s;
+
+    // Decompress public key
+    let mut pubkey_bytes = [0u8; 32];
+    pubkey_bytes.copy_from_slice(public_ed_key);
+    let compressed_pubkey = CompressedEdwardsY(pubkey_bytes);
+    let a_point = match compressed_pubkey.decompress() {
+        Some(point) => point,
+        None => return false,
+    };
+
+    // Compute challenge hash as scalar directly
+    let mut hasher = Sha512::new();
+    hasher.update(decoded_signature.r_bytes);
+    hasher.update(public_ed_key);
+    hasher.update(message);
+    let k = hash_to_scalar(hasher);
+
+    // Compute verification equation
+    let s_b = s * ED25519_BASEPOINT_POINT;
+    let k_a = k * a_point;
+    let expected = r + k_a;
+
+    s_b == expected
+}
+
+// Strict verification (verify_signature_strict and verify_strict) additionally requires:
+// - A and R to be canonical encodings (y < p and no negative zero), checked by re-encoding the decoded point
+// - A and R not to be of small order, with a small order A a signature with R = -kA and s = 0 verifies for any message
+// The equation is checked cofactorless, R == sB - kA compared as encodings, like RFC 8032 section 5.1.7 without the
+// optional factor 8, libsignal and ed25519-dalek's verify_strict. A cofactored verifier (8R == 8(sB - kA)) would also
+// accept signatures whose R or A carries a torsion component, so a signature can verify under one policy and not the other.
+
+// This function decodes a point for strict verification, rejecting non-canonical encodings and points of small order
+pub fn decode_point_strict(bytes: &[u8; 32]) -> Option<EdwardsPoint> {
+    let point = CompressedEdwardsY(*bytes).decompress()?;
+    if point.compress().as_bytes() != bytes || point.is_small_order() {
+        return None;
+    }
+    Some(point)
+}
+
+// This function checks R || s against the already validated public key A with the strict policy above
+fn verify_cofactorless_strict(signature: &[u8; 64], message: &[u8], a_bytes: &[u8; 32], a_point: &EdwardsPoint) -> bool {
+    let mut r_bytes = [0u8; 32];
+    let mut s_bytes = [0u8; 32];
+    r_bytes.copy_from_slice(&signature[..32]);
+    s_bytes.copy_from_slice(&signature[32..]);
+
+    if decode_point_strict(&r_bytes).is_none() {
+        return false;
+    }
+
+    let s_ctopt = Scalar::from_canonical_bytes(s_bytes);
+    if s_ctopt.is_none().into() {
+        return false;
+    }
+    let s = s_ctopt.unwrap();
+
+    let mut hasher = Sha512::new();
+    hasher.update(r_bytes);
+    hasher.update(a_bytes);
+    hasher.update(message);
+    let k = hash_to_scalar(hasher);
+
+    // Rcheck = sB - kA
+    let r_check = EdwardsPoint::vartime_double_scalar_mul_basepoint(&-k, a_point, &s);
+
+    r_check.compress().to_bytes() == r_bytes
+}
+
+// Verify an Ed25519 signature against an Edwards public key with the strict policy
+// Returns true if the signature is valid, false otherwise
+pub fn verify_signature_strict(signature: &[u8], message: &[u8], public_ed_key: &[u8]) -> bool {
+    let (Ok(signature), Ok(public_ed_key)) = (<&[u8; 64]>::try_from(signature), <&[u8; 32]>::try_from(public_ed_key)) else {
+        return false;
+    };
+
+    match decode_point_strict(public_ed_key) {
+        Some(a_point) => verify_cofactorless_strict(signature, message, public_ed_key, &a_point),
+        None => false,
+    }
+}
+
+// calculate_key_pair from the XEdDSA spec
+// E = kB, A is E with the sign bit forced to 0, and a = -k when E had its sign bit set so that A = aB still holds
+pub fn calculate_key_pair(private_key: &PrivateKey) -> ([u8; 32], Zeroizing<Scalar>) {
+    let k = Zeroizing::new(Scalar::from_bytes_mod_order(*private_key.to_bytes()));
+
+    let mut public_key = (*k * ED25519_BASEPOINT_POINT).compress().to_bytes();
+    let sign_bit = public_key[31] >> 7;
+    public_key[31] &= 0x7F;
+
+    let a = Zeroizing::new(if sign_bit == 1 { -*k } else { *k });
+
+    (public_key, a)
+}
+
+// xeddsa_sign from the XEdDSA spec
+// r = hash1(a || M || Z) mod L, R = rB, h = SHA512(R || A || M) mod L, s = r + ha mod L
+pub fn sign(private_key: &PrivateKey, message: &[u8], random: &[u8; 64]) -> [u8; 64] {
+    let (public_key, a) = calculate_key_pair(private_key);
+
+    let mut hasher = Sha512::new();
+    hasher.update(HASH1_PREFIX);
+    hasher.update(a.as_bytes());
+    hasher.update(message);
+    hasher.update(random);
+    let r = secret_hash_to_scalar(hasher);
+
+    let r_point = (*r * ED25519_BASEPOINT_POINT).compress().to_bytes();
+
+    let mut hasher = Sha512::new();
+    hasher.update(r_point);
+    hasher.update(public_key);
+    hasher.update(message);
+    let h = hash_to_scalar(hasher);
+
+    let s = *r + h * *a;
+
+    let mut signature = [0u8; 64];
+    signature[..32].copy_from_slice(&r_point);
+    signature[32..].copy_from_slice(s.as_bytes());
+    signature
+}
+
+// This function checks that u is a canonical field element (u < p)
+fn is_canonical_field_element(u: &[u8; 32]) -> bool {
+    // Compare from the most significant byte down
+    for i in (0..32).rev() {
+        if u[i] != FIELD_PRIME[i] {
+            return u[i] < FIELD_PRIME[i];
+        }
+    }
+    false
+}
+
+// libsignal signs with the Edwards key as it is and stores its sign bit in the unused top bit of s
+// Signatures from sign always leave that bit 0, so A keeps the sign bit 0 of the XEdDSA spec for them
+// This function returns the sign bit of A and the signature with the bit cleared
+fn split_sign_bit(signature: &[u8; 64]) -> (u8, [u8; 64]) {
+    let mut signature = *signature;
+    let sign_bit = signature[63] >> 7;
+    signature[63] &= 0x7F;
+    (sign_bit, signature)
+}
+
+// xeddsa_verify from the XEdDSA spec, also accepting the sign bit libsignal puts in s
+// A is the Edwards point of u with that sign bit (y = (u - 1) / (u + 1)), then R must equal sB - hA
+pub fn verify(public_key: &PublicKey, message: &[u8], signature: &[u8; 64]) -> bool {
+    let u = public_key.as_bytes();
+    if !is_canonical_field_element(u) {
+        return false;
+    }
+
+    let (sign_bit, signature) = split_sign_bit(signature);
+    let a_point = match MontgomeryPoint(*u).to_edwards(sign_bit) {
+        Some(point) => point,
+        None => return false,
+    };
+    let a_bytes = a_point.compress().to_bytes();
+
+    let mut r_bytes = [0u8; 32];
+    let mut s_bytes = [0u8; 32];
+    r_bytes.copy_from_slice(&signature[..32]);
+    s_bytes.copy_from_slice(&signature[32..]);
+
+    let s_ctopt = Scalar::from_canonical_bytes(s_bytes);
+    if s_ctopt.is_none().into() {
+        return false;
+    }
+    let s = s_ctopt.unwrap();
+
+    let mut hasher = Sha512::new();
+    hasher.update(r_bytes);
+    hasher.update(a_bytes);
+    hasher.update(message);
+    let h = hash_to_scalar(hasher);
+
+    // Rcheck = sB - hA
+    let r_check = EdwardsPoint::vartime_double_scalar_mul_basepoint(&-h, &a_point, &s);
+
+    r_check.compress().to_bytes() == r_bytes
+}
+
+// xeddsa_verify with the strict policy, used for signed prekeys
+// A is derived from u so its encoding is canonical, it still has to be rejected when u is a point of small order
+pub fn verify_strict(public_key: &PublicKey, message: &[u8], signature: &[u8; 64]) -> bool {
+    let u = public_key.as_bytes();
+    if !is_canonical_field_element(u) {
+        return false;
+    }
+
+    let (sign_bit, signature) = split_sign_bit(signature);
+    let a_point = match MontgomeryPoint(*u).to_edwards(sign_bit) {
+        Some(point) if !point.is_small_order() => point,
+        _ => return false,
+    };
+
+    verify_cofactorless_strict(&signature, message, &a_point.compress().to_bytes(), &a_point)
+}
@@ -0,0 +1,122 @@
+use echo_crypto::EchoError;
+use echo_crypto::aead::{aes_gcm_decrypt, aes_gcm_encrypt, message_header_bytes, open_envelope, seal_envelope, ENVELOPE_VERSION};
+
+fn bytes(hex_str: &str) -> Vec<u8> {
+    hex::decode(hex_str).unwrap()
+}
+
+// AES-256-GCM test vectors from "The Galois/Counter Mode of Operation (GCM)", McGrew and Viega (NIST submission)
+struct GcmVector {
+    key: &'static str,
+    nonce: &'static str,
+    plaintext: &'static str,
+    aad: &'static str,
+    ciphertext: &'static str,
+    tag: &'static str,
+}
+
+const GCM_VECTORS: [GcmVector; 4] = [
+    // Test case 13
+    GcmVector {
+        key: "0000000000000000000000000000000000000000000000000000000000000000",
+        nonce: "000000000000000000000000",
+        plaintext: "",
+        aad: "",
+        ciphertext: "",
+        tag: "530f8afbc74536b9a963b4f1c4cb738b",
+    },
+    // Test case 14
+    GcmVector {
+        key: "0000000000000000000000000000000000000000000000000000000000000000",
+        nonce: "000000000000000000000000",
+        plaintext: "00000000000000000000000000000000",
+        aad: "",
+        ciphertext: "cea7403d4d606b6e074ec5d3baf39d18",
+        tag: "d0d1c8a799996bf0265b98b5d48ab919",
+    },
+    // Test case 15
+    GcmVector {
+        key: "feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308",
+        nonce: "cafebabefacedbaddecaf888",
+        plaintext: "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b391aafd255",
+        aad: "",
+        ciphertext: "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662898015ad",
+        tag: "b094dac5d93471bdec1a502270e3cc6c",
+    },
+    // Test case 16
+    GcmVector {
+        key: "feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308",
+        nonce: "cafebabefacedbaddecaf888",
+        plaintext: "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+        aad: "feedfacedeadbeeffeedfacedeadbeefabaddad2",
+        ciphertext: "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662",
+        tag: "76fc6ece0f4e1768cddf8853bb2d551b",
+    },
+];
+
+#[test]
+fn aes_256_gcm_known_answers() {
+    for vector in &GCM_VECTORS {
+        let key = bytes(vector.key);
+        let nonce = bytes(vector.nonce);
+        let plaintext = bytes(vector.plaintext);
+        let aad = bytes(vector.aad);
+
+        let mut expected = bytes(vector.ciphertext);
+        expected.extend_from_slice(&bytes(vector.tag));
+
+        assert_eq!(aes_gcm_encrypt(&plaintext, &key, &nonce, &aad).unwrap(), expected);
+        assert_eq!(aes_gcm_decrypt(&expected, &key, &nonce, &aad).unwrap(), plaintext);
+    }
+}
+
+#[test]
+fn aes_256_gcm_rejects_modified_tag_and_aad() {
+    let vector = &GCM_VECTORS[3];
+    let key = bytes(vector.key);
+    let nonce = bytes(vector.nonce);
+
+    let mut sealed = bytes(vector.ciphertext);
+    sealed.extend_from_slice(&bytes(vector.tag));
+
+    assert!(aes_gcm_decrypt(&sealed, &key, &nonce, b"other associated data").is_err());
+
+    let last = sealed.len() - 1;
+    sealed[last] ^= 0x01;
+    assert!(aes_gcm_decrypt(&sealed, &key, &nonce, &bytes(vector.aad)).is_err());
+}
+
+#[test]
+fn seal_and_open_round_trip_with_fresh_nonces() {
+    let key = [0x42u8; 32];
+    let header = message_header_bytes("alice", "bob", 3, &[0x07u8; 32]).unwrap();
+
+    let first = seal_envelope(b"voice note", &key, &header).unwrap();
+    let second = seal_envelope(b"voice note", &key, &header).unwrap();
+
+    assert_eq!(first[0], ENVELOPE_VERSION);
+    assert_ne!(first[1..13], second[1..13]);
+    assert_eq!(open_envelope(&first, &key, &header).unwrap(), b"voice note");
+
+    let other_header = message_header_bytes("mallory", "bob", 3, &[0x07u8; 32]).unwrap();
+    assert!(open_envelope(&first, &key, &other_header).is_err());
+}
+
+#[test]
+fn message_header_encoding_is_unambiguous() {
+    let ephemeral_key = [0x01u8; 32];
+
+    assert_ne!(
+        message_header_bytes("ab", "c", 0, &ephemeral_key).unwrap(),
+        message_header_bytes("a", "bc", 0, &ephemeral_key).unwrap()
+    );
+    assert_eq!(message_header_bytes("a", "b", 0, &[0u8; 31]), Err(EchoError::InvalidPublicKey));
+}
+
+#[test]
+fn invalid_lengths_are_reported_with_their_error() {
+    assert_eq!(aes_gcm_encrypt(b"text", &[0u8; 31], &[0u8; 12], &[]), Err(EchoError::InvalidSymmetricKey));
+    assert_eq!(aes_gcm_encrypt(b"text", &[0u8; 32], &[0u8; 16], &[]), Err(EchoError::InvalidNonce));
+    assert_eq!(open_envelope(&[ENVELOPE_VERSION; 28], &[0u8; 32], &[]), Err(EchoError::MalformedEnvelope));
+    assert_eq!(open_envelope(&[2u8; 29], &[0u8; 32], &[]), Err(EchoError::UnsupportedVersion));
+}
@@ -0,0 +1,278 @@
+use echo_crypto::kdf::hkdf_sha256;
+use echo_crypto::{
+    x3dh, EchoError, KeyPair, OneTimePreKeyBatch, PreKeyBundle, PrivateKey, PublicKey, SignedPreKey, TrustStore, TrustedIdentity,
+};
+
+fn bytes(hex_str: &str) -> Vec<u8> {
+    hex::decode(hex_str).unwrap()
+}
+
+fn x25519(scalar: &[u8], u: &[u8]) -> Vec<u8> {
+    PrivateKey::from_bytes(scalar).unwrap().diffie_hellman(&PublicKey::from_bytes(u).unwrap()).unwrap().to_vec()
+}
+
+// This function accepts an identity key the way a first contact does
+fn trusted(identity_key: &PublicKey) -> TrustedIdentity {
+    TrustStore::new().ensure_trusted("contact", identity_key).unwrap()
+}
+
+fn public_key(private_key: &[u8]) -> PublicKey {
+    PrivateKey::from_bytes(private_key).unwrap().public_key()
+}
+
+// Points of order 1, 2, 4 and 8 on Curve25519 and its twist, including the non-canonical encodings p - 1, p and p + 1
+const LOW_ORDER_POINTS: [&str; 7] = [
+    "0000000000000000000000000000000000000000000000000000000000000000",
+    "0100000000000000000000000000000000000000000000000000000000000000",
+    "e0eb7a7c3b41b8ae1656e3faf19fc46ada098deb9c32b1fd866205165f49b800",
+    "5f9c95bca3508c24b1d0b1559c83ef5b04445cc4581c8e86d8224eddd09f1157",
+    "ecffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+    "edffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+    "eeffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff7f",
+];
+
+// RFC 7748 section 5.2, first test vector
+#[test]
+fn x25519_rfc7748_vector_1() {
+    let scalar = bytes("a546e36bf0527c9d3b16154b82465edd62144c0ac1fc5a18506a2244ba449ac4");
+    let u = bytes("e6db6867583030db3594c1a424b15f7c726624ec26b3353b10a903a6d0ab1c4c");
+
+    assert_eq!(
+        x25519(&scalar, &u),
+        bytes("c3da55379de9c6908e94ea4df28d084f32eccf03491c71f754b4075577a28552")
+    );
+}
+
+// RFC 7748 section 5.2, second test vector (the high bit of u must be ignored)
+#[test]
+fn x25519_rfc7748_vector_2() {
+    let scalar = bytes("4b66e9d4d1b4673c5ad22691957d6af5c11b6421e0ea01d42ca4169e7918ba0d");
+    let u = bytes("e5210f12786811d3f4b7959d0538ae2c31dbe7106fc03c3efc4cd549c715a493");
+
+    assert_eq!(
+        x25519(&scalar, &u),
+        bytes("95cbde9476e8907d7aade45cb4b873f88b595a68799fa152e6f8f7647aac7957")
+    );
+}
+
+// RFC 7748 section 5.2, iterated test (1 and 1,000 iterations)
+#[test]
+fn x25519_rfc7748_iterated() {
+    let mut k = [0u8; 32];
+    k[0] = 9;
+    let mut u = k;
+
+    for iteration in 1..=1000 {
+        let result: [u8; 32] = x25519(&k, &u).try_into().unwrap();
+        u = k;
+        k = result;
+
+        if iteration == 1 {
+            assert_eq!(k.to_vec(), bytes("422c8e7a6227d7bca1350b3e2bb7279f7897b87bb6854b783c60e80311ae3079"));
+        }
+    }
+
+    assert_eq!(k.to_vec(), bytes("684cf59ba83309552800ef566f2f4d3c1c3887c49360e3875f2eb94d99532c51"));
+}
+
+// RFC 7748 section 6.1, Diffie-Hellman between Alice and Bob
+#[test]
+fn x25519_rfc7748_key_agreement() {
+    let alice_private = bytes("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a");
+    let alice_public = bytes("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a");
+    let bob_private = bytes("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb");
+    let bob_public = bytes("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f");
+    let shared = bytes("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+
+    assert_eq!(public_key(&alice_private).as_bytes().to_vec(), alice_public);
+    assert_eq!(public_key(&bob_private).as_bytes().to_vec(), bob_public);
+    assert_eq!(x25519(&alice_private, &bob_public), shared);
+    assert_eq!(x25519(&bob_private, &alice_public), shared);
+}
+
+// RFC 5869 appendix A.1, basic test case with SHA-256
+#[test]
+fn hkdf_rfc5869_test_case_1() {
+    let ikm = [0x0bu8; 22];
+    let salt = bytes("000102030405060708090a0b0c");
+    let info = bytes("f0f1f2f3f4f5f6f7f8f9");
+
+    assert_eq!(
+        *hkdf_sha256(&ikm, &salt, &info, 42).unwrap(),
+        bytes("3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865")
+    );
+}
+
+// RFC 5869 appendix A.3, zero-length salt and info
+#[test]
+fn hkdf_rfc5869_test_case_3() {
+    let ikm = [0x0bu8; 22];
+
+    assert_eq!(
+        *hkdf_sha256(&ikm, &[], &[], 42).unwrap(),
+        bytes("8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8")
+    );
+}
+
+#[test]
+fn x3dh_initiator_and_responder_agree() {
+    let identity_a = PrivateKey::from_bytes(&bytes("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a")).unwrap();
+    let ephemeral_a = PrivateKey::from_bytes(&[0x11u8; 32]).unwrap();
+    let identity_b = PrivateKey::from_bytes(&bytes("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb")).unwrap();
+    let signed_prekey_b = SignedPreKey::sign(1, KeyPair::from_random_bytes(&[0x22u8; 32]).unwrap(), &identity_b, &[0x44u8; 64]);
+    let one_time_prekey_b = PrivateKey::from_bytes(&[0x33u8; 32]).unwrap();
+
+    for use_one_time_prekey in [true, false] {
+        let one_time_prekey_public = one_time_prekey_b.public_key();
+        let bundle = PreKeyBundle::from_keys(
+            identity_b.public_key().as_bytes(),
+            1,
+            signed_prekey_b.key_pair.public_key.as_bytes(),
+            &signed_prekey_b.signature,
+            use_one_time_prekey.then_some((7, &one_time_prekey_public.as_bytes()[..])),
+        ).unwrap();
+
+        let initiator = x3dh::initiate(&bundle.verify().unwrap(), &trusted(&identity_b.public_key()), &identity_a, &ephemeral_a).unwrap();
+        let responder = x3dh::respond(
+            &identity_b,
+            &signed_prekey_b.key_pair.private_key,
+            use_one_time_prekey.then_some(&one_time_prekey_b),
+            &trusted(&identity_a.public_key()),
+            &ephemeral_a.public_key(),
+        ).unwrap();
+
+        assert_eq!(initiator, responder);
+        assert_eq!(&initiator.associated_data[..32], identity_a.public_key().as_bytes());
+        assert_eq!(&initiator.associated_data[32..], identity_b.public_key().as_bytes());
+    }
+}
+
+#[test]
+fn prekey_bundle_round_trips_and_rejects_bad_input() {
+    let bundle = PreKeyBundle::from_keys(&[1u8; 32], 9, &[2u8; 32], &[3u8; 64], Some((4, &[5u8; 32]))).unwrap();
+    let encoded = bundle.to_bytes();
+
+    assert_eq!(encoded.len(), 170);
+    assert_eq!(PreKeyBundle::from_bytes(&encoded).unwrap(), bundle);
+    assert_eq!(PreKeyBundle::from_bytes(&encoded[..169]), Err(EchoError::MalformedBundle));
+
+    let mut bad_flag = encoded.clone();
+    bad_flag[133] = 2;
+    assert_eq!(PreKeyBundle::from_bytes(&bad_flag), Err(EchoError::MalformedBundle));
+
+    let mut bad_version = encoded.clone();
+    bad_version[0] = 2;
+    assert_eq!(PreKeyBundle::from_bytes(&bad_version), Err(EchoError::UnsupportedVersion));
+}
+
+#[test]
+fn signed_prekey_bundle_verifies_against_identity_key() {
+    let identity = KeyPair::from_random_bytes(&[0x41u8; 32]).unwrap();
+    let signed_prekey = SignedPreKey::sign(3, KeyPair::from_random_bytes(&[0x42u8; 32]).unwrap(), &identity.private_key, &[0x43u8; 64]);
+    let batch = OneTimePreKeyBatch::generate(10, 2, &[0x44u8; 64]).unwrap();
+
+    assert!(signed_prekey.verify(&identity.public_key));
+    assert!(!signed_prekey.verify(&signed_prekey.key_pair.public_key));
+
+    let bundle = PreKeyBundle::new(identity.public_key, &signed_prekey, batch.prekeys().get(1));
+    assert_eq!(bundle.signed_prekey_id, 3);
+    assert_eq!(bundle.signed_prekey, signed_prekey.key_pair.public_key);
+    assert_eq!(bundle.signed_prekey_signature, signed_prekey.signature);
+    assert_eq!(bundle.one_time_prekey, Some((11, batch.prekeys()[1].public_key)));
+}
+
+#[test]
+fn bundle_verification_rejects_forged_signed_prekeys() {
+    let identity = KeyPair::from_random_bytes(&[0x51u8; 32]).unwrap();
+    let attacker = KeyPair::from_random_bytes(&[0x52u8; 32]).unwrap();
+    let signed_prekey = SignedPreKey::sign(1, KeyPair::from_random_bytes(&[0x53u8; 32]).unwrap(), &identity.private_key, &[0x54u8; 64]);
+
+    let bundle = PreKeyBundle::new(identity.public_key, &signed_prekey, None);
+    assert_eq!(bundle.verify().unwrap().bundle(), &bundle);
+
+    // The server swaps in its own signed prekey but cannot sign it with the identity key
+    let mut swapped = bundle.clone();
+    swapped.signed_prekey = attacker.public_key;
+    assert_eq!(swapped.verify(), Err(EchoError::InvalidPreKeySignature));
+
+    // A signature made by a different identity key
+    let forged = SignedPreKey::sign(1, KeyPair::from_random_bytes(&[0x55u8; 32]).unwrap(), &attacker.private_key, &[0x56u8; 64]);
+    assert_eq!(PreKeyBundle::new(identity.public_key, &forged, None).verify(), Err(EchoError::InvalidPreKeySignature));
+
+    let mut zero_signature = bundle;
+    zero_signature.signed_prekey_signature = [0u8; 64];
+    assert_eq!(zero_signature.verify(), Err(EchoError::InvalidPreKeySignature));
+}
+
+#[test]
+fn diffie_hellman_rejects_low_order_public_keys() {
+    for seed in [0x11u8, 0x77, 0xEE] {
+        let private_key = PrivateKey::from_bytes(&[seed; 32]).unwrap();
+        for point in LOW_ORDER_POINTS {
+            let their_public_key = PublicKey::from_bytes(&bytes(point)).unwrap();
+            assert_eq!(private_key.diffie_hellman(&their_public_key).err(), Some(EchoError::LowOrderPublicKey));
+        }
+    }
+}
+
+#[test]
+fn x3dh_rejects_low_order_keys() {
+    let identity_a = PrivateKey::from_bytes(&[0x11u8; 32]).unwrap();
+    let ephemeral_a = PrivateKey::from_bytes(&[0x12u8; 32]).unwrap();
+    let identity_b = PrivateKey::from_bytes(&[0x21u8; 32]).unwrap();
+    let signed_prekey_b = SignedPreKey::sign(1, KeyPair::from_random_bytes(&[0x22u8; 32]).unwrap(), &identity_b, &[0x44u8; 64]);
+
+    for point in LOW_ORDER_POINTS {
+        let low_order = PublicKey::from_bytes(&bytes(point)).unwrap();
+
+        // A server handing out a low order prekey, correctly signed by the identity key
+        let signature = echo_crypto::xeddsa::sign(&identity_b, low_order.as_bytes(), &[0x45u8; 64]);
+        let bundle = PreKeyBundle::from_keys(identity_b.public_key().as_bytes(), 1, low_order.as_bytes(), &signature, None).unwrap();
+        assert_eq!(
+            x3dh::initiate(&bundle.verify().unwrap(), &trusted(&identity_b.public_key()), &identity_a, &ephemeral_a).err(),
+            Some(EchoError::LowOrderPublicKey)
+        );
+
+        // A low order one-time prekey next to a valid signed prekey
+        let bundle = PreKeyBundle::from_keys(
+            identity_b.public_key().as_bytes(),
+            1,
+            signed_prekey_b.key_pair.public_key.as_bytes(),
+            &signed_prekey_b.signature,
+            Some((7, low_order.as_bytes())),
+        ).unwrap();
+        assert_eq!(
+            x3dh::initiate(&bundle.verify().unwrap(), &trusted(&identity_b.public_key()), &identity_a, &ephemeral_a).err(),
+            Some(EchoError::LowOrderPublicKey)
+        );
+
+        // A low order ephemeral or identity key from the initiator
+        for (their_identity, their_ephemeral) in [(identity_a.public_key(), low_order), (low_order, ephemeral_a.public_key())] {
+            let their_identity = trusted(&their_identity);
+            let result = x3dh::respond(&identity_b, &signed_prekey_b.key_pair.private_key, None, &their_identity, &their_ephemeral);
+            assert_eq!(result.err(), Some(EchoError::LowOrderPublicKey));
+        }
+    }
+}
+
+// X3DH only runs against the identity key the trust store accepted for the contact
+#[test]
+fn x3dh_initiator_requires_the_trusted_identity_key() {
+    let identity_a = PrivateKey::from_bytes(&[0x11u8; 32]).unwrap();
+    let ephemeral_a = PrivateKey::from_bytes(&[0x12u8; 32]).unwrap();
+    let identity_b = PrivateKey::from_bytes(&[0x21u8; 32]).unwrap();
+    let signed_prekey_b = SignedPreKey::sign(1, KeyPair::from_random_bytes(&[0x22u8; 32]).unwrap(), &identity_b, &[0x44u8; 64]);
+    let bundle = PreKeyBundle::new(identity_b.public_key(), &signed_prekey_b, None).verify().unwrap();
+
+    let mut trust_store = TrustStore::new();
+    let remembered = trust_store.ensure_trusted("bob", &PublicKey::from([0x0Bu8; 32])).unwrap();
+    assert_eq!(
+        x3dh::initiate(&bundle, &remembered, &identity_a, &ephemeral_a).err(),
+        Some(EchoError::UntrustedIdentityKey)
+    );
+
+    // The server now serves a different identity key for bob, it is only usable once approved
+    assert_eq!(trust_store.ensure_trusted("bob", &identity_b.public_key()).err(), Some(EchoError::IdentityKeyChanged));
+    let approved = trust_store.approve("bob", &identity_b.public_key());
+    assert!(x3dh::initiate(&bundle, &approved, &identity_a, &ephemeral_a).is_ok());
+}
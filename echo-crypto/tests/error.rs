@@ -0,0 +1,57 @@
+use std::collections::HashSet;
+
+use echo_crypto::EchoError;
+
+const ALL_ERRORS: [EchoError; 34] = [
+    EchoError::InvalidPrivateKey,
+    EchoError::InvalidPublicKey,
+    EchoError::InvalidSymmetricKey,
+    EchoError::InvalidNonce,
+    EchoError::InvalidSignature,
+    EchoError::InvalidOutputLength,
+    EchoError::NotEnoughRandomBytes,
+    EchoError::RandomnessUnavailable,
+    EchoError::EncryptionFailed,
+    EchoError::DecryptionFailed,
+    EchoError::InvalidEncoding,
+    EchoError::MalformedEnvelope,
+    EchoError::MalformedBundle,
+    EchoError::MalformedMessageHeader,
+    EchoError::UnsupportedVersion,
+    EchoError::InvalidPreKeyBatch,
+    EchoError::SessionNotReady,
+    EchoError::DuplicateMessage,
+    EchoError::TooManySkippedMessages,
+    EchoError::InvalidScalar,
+    EchoError::InvalidPoint,
+    EchoError::KeyNotFound,
+    EchoError::KeyStoreFull,
+    EchoError::MalformedKeyStore,
+    EchoError::WrongPassword,
+    EchoError::InvalidKdfParams,
+    EchoError::MalformedVault,
+    EchoError::MalformedSessionRecord,
+    EchoError::MalformedFingerprint,
+    EchoError::IdentityKeyChanged,
+    EchoError::MalformedTrustStore,
+    EchoError::UntrustedIdentityKey,
+    EchoError::InvalidPreKeySignature,
+    EchoError::LowOrderPublicKey,
+];
+
+// The UI matches on codes, so every error needs its own code in SCREAMING_SNAKE_CASE
+#[test]
+fn error_codes_are_unique_and_well_formed() {
+    let codes: HashSet<&str> = ALL_ERRORS.iter().map(EchoError::code).collect();
+    assert_eq!(codes.len(), ALL_ERRORS.len());
+
+    for code in codes {
+        assert!(code.chars().all(|c| c.is_ascii_uppercase() || c == '_'), "{code}");
+    }
+}
+
+#[test]
+fn display_uses_the_message() {
+    assert_eq!(EchoError::DecryptionFailed.to_string(), "Decryption failed");
+    assert_eq!(EchoError::DecryptionFailed.code(), "DECRYPTION_FAILED");
+}
@@ -0,0 +1,72 @@
+use echo_crypto::fingerprint::SCANNABLE_VERSION;
+use echo_crypto::{EchoError, Fingerprint, PublicKey};
+
+const ALICE_ID: &[u8] = b"alice-user-id";
+const BOB_ID: &[u8] = b"bob-user-id";
+
+fn keys() -> (PublicKey, PublicKey) {
+    (PublicKey::from([0xA1u8; 32]), PublicKey::from([0xB2u8; 32]))
+}
+
+#[test]
+fn both_users_see_the_same_safety_number() {
+    let (alice_key, bob_key) = keys();
+    let alice_view = Fingerprint::new(ALICE_ID, &alice_key, BOB_ID, &bob_key);
+    let bob_view = Fingerprint::new(BOB_ID, &bob_key, ALICE_ID, &alice_key);
+
+    let safety_number = alice_view.safety_number();
+    assert_eq!(safety_number, bob_view.safety_number());
+    assert_eq!(safety_number.len(), 60);
+    assert!(safety_number.bytes().all(|digit| digit.is_ascii_digit()));
+}
+
+// Computed independently with Python's hashlib from the algorithm description
+#[test]
+fn safety_number_known_answer() {
+    let (alice_key, bob_key) = keys();
+    assert_eq!(
+        Fingerprint::new(ALICE_ID, &alice_key, BOB_ID, &bob_key).safety_number(),
+        "324148559330803872748166155218610630756465789645932778347342"
+    );
+}
+
+#[test]
+fn safety_number_changes_with_either_key_or_id() {
+    let (alice_key, bob_key) = keys();
+    let expected = Fingerprint::new(ALICE_ID, &alice_key, BOB_ID, &bob_key).safety_number();
+
+    let mitm_key = PublicKey::from([0xC3u8; 32]);
+    assert_ne!(Fingerprint::new(ALICE_ID, &alice_key, BOB_ID, &mitm_key).safety_number(), expected);
+    assert_ne!(Fingerprint::new(ALICE_ID, &mitm_key, BOB_ID, &bob_key).safety_number(), expected);
+    assert_ne!(Fingerprint::new(ALICE_ID, &alice_key, b"mallory", &bob_key).safety_number(), expected);
+}
+
+#[test]
+fn scanned_payload_matches_only_the_other_side() {
+    let (alice_key, bob_key) = keys();
+    let alice_view = Fingerprint::new(ALICE_ID, &alice_key, BOB_ID, &bob_key);
+    let bob_view = Fingerprint::new(BOB_ID, &bob_key, ALICE_ID, &alice_key);
+
+    assert_eq!(alice_view.matches_scanned(&bob_view.scannable()), Ok(true));
+    assert_eq!(bob_view.matches_scanned(&alice_view.scannable()), Ok(true));
+
+    // Scanning your own screen, or a screen showing a different key, is not a match
+    assert_eq!(alice_view.matches_scanned(&alice_view.scannable()), Ok(false));
+    let mitm_view = Fingerprint::new(BOB_ID, &PublicKey::from([0xC3u8; 32]), ALICE_ID, &alice_key);
+    assert_eq!(alice_view.matches_scanned(&mitm_view.scannable()), Ok(false));
+}
+
+#[test]
+fn malformed_scans_are_rejected() {
+    let (alice_key, bob_key) = keys();
+    let view = Fingerprint::new(ALICE_ID, &alice_key, BOB_ID, &bob_key);
+    let scannable = view.scannable();
+
+    assert_eq!(scannable[0], SCANNABLE_VERSION);
+    assert_eq!(view.matches_scanned(&[]), Err(EchoError::MalformedFingerprint));
+    assert_eq!(view.matches_scanned(&scannable[..64]), Err(EchoError::MalformedFingerprint));
+
+    let mut future = scannable;
+    future[0] = SCANNABLE_VERSION + 1;
+    assert_eq!(view.matches_scanned(&future), Err(EchoError::UnsupportedVersion));
+}
@@ -0,0 +1,218 @@
+use echo_crypto::{aead, kdf, x3dh, EchoError, KeyPair, KeyStore, PublicKey, TrustStore, TrustedIdentity};
+
+const WRAPPING_KEY: [u8; 32] = [0x42u8; 32];
+
+// This function accepts an identity key the way a first contact does
+fn trusted(identity_key: &PublicKey) -> TrustedIdentity {
+    TrustStore::new().ensure_trusted("contact", identity_key).unwrap()
+}
+
+fn provisioned_store() -> KeyStore {
+    let mut store = KeyStore::new();
+    store.generate_identity().unwrap();
+    store.generate_signed_prekey(1).unwrap();
+    store.generate_one_time_prekeys(100, 3).unwrap();
+    store
+}
+
+#[test]
+fn x3dh_between_two_stores_agrees() {
+    let mut alice = provisioned_store();
+    let mut bob = provisioned_store();
+
+    let bundle = bob.prekey_bundle(1, Some(101)).unwrap().verify().unwrap();
+    let ephemeral = alice.generate_ephemeral_key().unwrap();
+    let ephemeral_public = alice.public_key(ephemeral).unwrap();
+
+    let sent = alice.x3dh_initiate(&bundle, &trusted(&bob.identity_public_key().unwrap()), ephemeral).unwrap();
+    let received = bob
+        .x3dh_respond(1, Some(101), &trusted(&alice.identity_public_key().unwrap()), &ephemeral_public)
+        .unwrap();
+    assert_eq!(sent.associated_data, received.associated_data);
+
+    // Both sides hold the same SK, so a message sealed by one opens with the other
+    let alice_key = alice.derive_symmetric_key(sent.secret).unwrap();
+    let bob_key = bob.derive_symmetric_key(received.secret).unwrap();
+    let envelope = alice.seal(alice_key, b"hello bob", &sent.associated_data).unwrap();
+    assert_eq!(bob.open(bob_key, &envelope, &received.associated_data).unwrap(), b"hello bob");
+
+    // The ephemeral key and the one-time prekey are single use
+    assert!(!alice.contains(ephemeral));
+    assert_eq!(bob.one_time_prekey_count(), 2);
+    assert_eq!(
+        bob.x3dh_respond(1, Some(101), &trusted(&alice.identity_public_key().unwrap()), &ephemeral_public),
+        Err(EchoError::KeyNotFound)
+    );
+}
+
+#[test]
+fn store_matches_the_plain_x3dh_initiator() {
+    let mut bob = provisioned_store();
+    let bundle = bob.prekey_bundle(1, None).unwrap().verify().unwrap();
+    let alice_identity = KeyPair::from_random_bytes(&[0x11u8; 32]).unwrap();
+    let alice_ephemeral = KeyPair::from_random_bytes(&[0x22u8; 32]).unwrap();
+
+    let bob_identity = trusted(&bob.identity_public_key().unwrap());
+    let expected = x3dh::initiate(&bundle, &bob_identity, &alice_identity.private_key, &alice_ephemeral.private_key).unwrap();
+    let received = bob
+        .x3dh_respond(1, None, &trusted(&alice_identity.public_key), &alice_ephemeral.public_key)
+        .unwrap();
+
+    let key = bob.derive_symmetric_key(received.secret).unwrap();
+    let expected_key = kdf::derive_symmetric_key(&expected.shared_secret);
+    let envelope = aead::seal_envelope(b"hi", expected_key.as_ref(), b"").unwrap();
+    assert_eq!(bob.open(key, &envelope, b"").unwrap(), b"hi");
+}
+
+#[test]
+fn export_round_trips_under_the_wrapping_key() {
+    let mut store = provisioned_store();
+    let key = store.generate_symmetric_key().unwrap();
+    let ephemeral = store.generate_ephemeral_key().unwrap();
+    let envelope = store.seal(key, b"persisted", b"").unwrap();
+
+    let blob = store.export(&WRAPPING_KEY).unwrap();
+    let restored = KeyStore::import(&blob, &WRAPPING_KEY).unwrap();
+
+    assert_eq!(restored.identity_public_key(), store.identity_public_key());
+    assert_eq!(restored.public_key(ephemeral), store.public_key(ephemeral));
+    assert_eq!(restored.prekey_bundle(1, Some(102)), store.prekey_bundle(1, Some(102)));
+    assert_eq!(restored.open(key, &envelope, b"").unwrap(), b"persisted");
+
+    // Handles keep counting from where the exported store stopped
+    let mut restored = restored;
+    assert!(restored.generate_symmetric_key().unwrap() > ephemeral);
+}
+
+#[test]
+fn import_rejects_wrong_key_and_tampering() {
+    let blob = provisioned_store().export(&WRAPPING_KEY).unwrap();
+
+    assert_eq!(KeyStore::import(&blob, &[0x43u8; 32]).err(), Some(EchoError::DecryptionFailed));
+
+    let mut tampered = blob.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    assert_eq!(KeyStore::import(&tampered, &WRAPPING_KEY).err(), Some(EchoError::DecryptionFailed));
+
+    assert_eq!(KeyStore::import(&blob, &[0u8; 16]).err(), Some(EchoError::InvalidSymmetricKey));
+}
+
+// This function decrypts an exported store, applies the edit and encrypts the result again under the same key
+fn edited(store: &KeyStore, edit: impl Fn(&mut Vec<u8>)) -> Vec<u8> {
+    let mut bytes = aead::open_envelope(&store.export(&WRAPPING_KEY).unwrap(), &WRAPPING_KEY, b"EchoKeyStore").unwrap();
+    edit(&mut bytes);
+    aead::seal_envelope(&bytes, &WRAPPING_KEY, b"EchoKeyStore").unwrap()
+}
+
+// A blob that authenticates but repeats an ID or handle would decode to a different store, so it is rejected
+#[test]
+fn import_rejects_duplicate_ids_and_handles() {
+    let mut store = KeyStore::new();
+    store.generate_identity().unwrap();
+    store.generate_signed_prekey(1).unwrap();
+    store.generate_signed_prekey(2).unwrap();
+    store.generate_one_time_prekeys(100, 2).unwrap();
+    let first = store.generate_symmetric_key().unwrap();
+    store.generate_symmetric_key().unwrap();
+    assert!(KeyStore::import(&edited(&store, |_| {}), &WRAPPING_KEY).is_ok());
+
+    // VERSION || NEXT_HANDLE || HAS_IK || IK || SPK_COUNT || 2 SPKs of 100 bytes || OPK_COUNT || 2 OPKs of 36 bytes
+    // || KEY_COUNT || 2 keys of 37 bytes
+    let second_signed_prekey_id = 1 + 4 + 1 + 32 + 4 + 100;
+    let second_one_time_prekey_id = second_signed_prekey_id + 100 + 4 + 36;
+    let second_handle = second_one_time_prekey_id + 36 + 4 + 37;
+
+    let duplicate_signed_prekey = edited(&store, |bytes| bytes[second_signed_prekey_id..][..4].copy_from_slice(&1u32.to_be_bytes()));
+    let duplicate_one_time_prekey = edited(&store, |bytes| bytes[second_one_time_prekey_id..][..4].copy_from_slice(&100u32.to_be_bytes()));
+    let duplicate_handle = edited(&store, |bytes| bytes[second_handle..][..4].copy_from_slice(&first.to_be_bytes()));
+    // A handle at or above NEXT_HANDLE would clash with the next key the store allocates
+    let handle_not_yet_allocated = edited(&store, |bytes| bytes[1..5].copy_from_slice(&(first + 1).to_be_bytes()));
+
+    for blob in [duplicate_signed_prekey, duplicate_one_time_prekey, duplicate_handle, handle_not_yet_allocated] {
+        assert_eq!(KeyStore::import(&blob, &WRAPPING_KEY).err(), Some(EchoError::MalformedKeyStore));
+    }
+}
+
+#[test]
+fn unknown_handles_and_missing_identity_are_reported() {
+    let mut store = KeyStore::new();
+
+    assert_eq!(store.identity_public_key(), Err(EchoError::KeyNotFound));
+    assert_eq!(store.generate_signed_prekey(1), Err(EchoError::KeyNotFound));
+    assert_eq!(store.public_key(7), Err(EchoError::KeyNotFound));
+
+    // A secret handle is not a key pair and the other way round
+    let secret = store.generate_symmetric_key().unwrap();
+    let ephemeral = store.generate_ephemeral_key().unwrap();
+    assert_eq!(store.public_key(secret), Err(EchoError::KeyNotFound));
+    assert_eq!(store.seal(ephemeral, b"", b"").err(), Some(EchoError::KeyNotFound));
+
+    store.remove(secret);
+    assert_eq!(store.seal(secret, b"", b"").err(), Some(EchoError::KeyNotFound));
+}
+
+// Both sides start the ratchet from their X3DH handles, SK and the signed prekey private key never leave the stores
+#[test]
+fn ratchet_starts_from_the_x3dh_handles() {
+    for header_encryption in [false, true] {
+        let mut alice = provisioned_store();
+        let mut bob = provisioned_store();
+
+        let bundle = bob.prekey_bundle(1, Some(100)).unwrap().verify().unwrap();
+        let ephemeral = alice.generate_ephemeral_key().unwrap();
+        let ephemeral_public = alice.public_key(ephemeral).unwrap();
+        let sent = alice.x3dh_initiate(&bundle, &trusted(&bob.identity_public_key().unwrap()), ephemeral).unwrap();
+        let received = bob
+            .x3dh_respond(1, Some(100), &trusted(&alice.identity_public_key().unwrap()), &ephemeral_public)
+            .unwrap();
+
+        assert_eq!(sent.remote_signed_prekey(), Some(&bundle.bundle().signed_prekey));
+        assert_eq!(received.remote_signed_prekey(), None);
+
+        let mut alice_session = alice.ratchet_initiate(&sent, header_encryption).unwrap();
+        let mut bob_session = bob.ratchet_respond(&received, 1, header_encryption).unwrap();
+        assert_eq!(alice_session.has_header_encryption(), header_encryption);
+
+        let message = alice_session.encrypt(b"hello bob").unwrap();
+        assert_eq!(bob_session.decrypt(&message).unwrap(), b"hello bob");
+        let reply = bob_session.encrypt(b"hello alice").unwrap();
+        assert_eq!(alice_session.decrypt(&reply).unwrap(), b"hello alice");
+
+        // SK is consumed, the signed prekey is kept for the next initiator
+        assert!(!alice.contains(sent.secret));
+        assert!(!bob.contains(received.secret));
+        assert_eq!(bob.ratchet_respond(&received, 1, header_encryption).err(), Some(EchoError::KeyNotFound));
+        assert!(bob.prekey_bundle(1, None).is_ok());
+    }
+}
+
+#[test]
+fn ratchet_respond_needs_a_known_signed_prekey() {
+    let mut bob = provisioned_store();
+    let alice = KeyPair::from_random_bytes(&[0x11u8; 32]).unwrap();
+    let ephemeral = KeyPair::from_random_bytes(&[0x22u8; 32]).unwrap();
+    let received = bob.x3dh_respond(1, None, &trusted(&alice.public_key), &ephemeral.public_key).unwrap();
+
+    assert_eq!(bob.ratchet_respond(&received, 2, false).err(), Some(EchoError::KeyNotFound));
+
+    // A responder result names no remote signed prekey, so it cannot start an initiator session
+    assert_eq!(bob.ratchet_initiate(&received, false).err(), Some(EchoError::KeyNotFound));
+
+    // A failed start leaves SK in place
+    assert!(bob.contains(received.secret));
+}
+
+#[test]
+fn x3dh_initiate_refuses_an_identity_key_the_trust_store_did_not_accept() {
+    let mut alice = provisioned_store();
+    let bob = provisioned_store();
+    let bundle = bob.prekey_bundle(1, None).unwrap().verify().unwrap();
+    let ephemeral = alice.generate_ephemeral_key().unwrap();
+
+    let someone_else = trusted(&PublicKey::from([0x0Cu8; 32]));
+    assert_eq!(alice.x3dh_initiate(&bundle, &someone_else, ephemeral), Err(EchoError::UntrustedIdentityKey));
+
+    // The ephemeral key is only consumed by a successful run
+    assert!(alice.contains(ephemeral));
+}
@@ -0,0 +1,230 @@
+use echo_crypto::{EchoError, KeyPair};
+use echo_crypto::ratchet::{MessageHeader, RatchetSession, ENCRYPTED_HEADER_LENGTH, HEADER_LENGTH, MAX_SKIP};
+
+const SHARED_SECRET: [u8; 32] = [0x5Au8; 32];
+const ASSOCIATED_DATA: &[u8] = b"IK_A || IK_B";
+
+fn session_pair() -> (RatchetSession, RatchetSession) {
+    let signed_prekey = KeyPair::from_random_bytes(&[0x24u8; 32]).unwrap();
+
+    let alice = RatchetSession::new_initiator(&SHARED_SECRET, &signed_prekey.public_key, ASSOCIATED_DATA).unwrap();
+    let bob = RatchetSession::new_responder(&SHARED_SECRET, signed_prekey, ASSOCIATED_DATA);
+
+    (alice, bob)
+}
+
+fn header_encrypted_pair() -> (RatchetSession, RatchetSession) {
+    let signed_prekey = KeyPair::from_random_bytes(&[0x24u8; 32]).unwrap();
+
+    let alice = RatchetSession::new_initiator_with_header_encryption(&SHARED_SECRET, &signed_prekey.public_key, ASSOCIATED_DATA).unwrap();
+    let bob = RatchetSession::new_responder_with_header_encryption(&SHARED_SECRET, signed_prekey, ASSOCIATED_DATA);
+
+    (alice, bob)
+}
+
+#[test]
+fn messages_round_trip_across_dh_ratchet_steps() {
+    let (mut alice, mut bob) = session_pair();
+
+    for round in 0..4 {
+        let to_bob = alice.encrypt(format!("alice {round}").as_bytes()).unwrap();
+        assert_eq!(bob.decrypt(&to_bob).unwrap(), format!("alice {round}").as_bytes());
+
+        let to_alice = bob.encrypt(format!("bob {round}").as_bytes()).unwrap();
+        assert_eq!(alice.decrypt(&to_alice).unwrap(), format!("bob {round}").as_bytes());
+    }
+}
+
+#[test]
+fn header_carries_ratchet_key_and_counters() {
+    let (mut alice, mut bob) = session_pair();
+
+    alice.encrypt(b"0").unwrap();
+    let second = alice.encrypt(b"1").unwrap();
+    let header = MessageHeader::from_bytes(&second).unwrap();
+
+    assert_eq!(header.dh, alice.ratchet_public_key());
+    assert_eq!((header.pn, header.n), (0, 1));
+    assert_eq!(MessageHeader::from_bytes(&header.to_bytes()).unwrap(), header);
+
+    bob.decrypt(&second).unwrap();
+    let reply = bob.encrypt(b"reply").unwrap();
+    alice.decrypt(&reply).unwrap();
+
+    let after_ratchet = MessageHeader::from_bytes(&alice.encrypt(b"2").unwrap()).unwrap();
+    assert_ne!(after_ratchet.dh, header.dh);
+    assert_eq!((after_ratchet.pn, after_ratchet.n), (2, 0));
+}
+
+#[test]
+fn out_of_order_messages_use_skipped_keys_once() {
+    let (mut alice, mut bob) = session_pair();
+
+    let first = alice.encrypt(b"first").unwrap();
+    let second = alice.encrypt(b"second").unwrap();
+    let third = alice.encrypt(b"third").unwrap();
+
+    assert_eq!(bob.decrypt(&third).unwrap(), b"third");
+    assert_eq!(bob.skipped_message_key_count(), 2);
+
+    // A late message from the previous chain still decrypts after a DH ratchet step
+    let reply = bob.encrypt(b"reply").unwrap();
+    alice.decrypt(&reply).unwrap();
+    let next_chain = alice.encrypt(b"next chain").unwrap();
+    assert_eq!(bob.decrypt(&next_chain).unwrap(), b"next chain");
+
+    assert_eq!(bob.decrypt(&first).unwrap(), b"first");
+    assert_eq!(bob.decrypt(&second).unwrap(), b"second");
+    assert_eq!(bob.skipped_message_key_count(), 0);
+
+    // Keys are deleted after use, a replay fails
+    assert!(bob.decrypt(&first).is_err());
+}
+
+#[test]
+fn skipping_more_than_max_skip_is_rejected() {
+    let (mut alice, mut bob) = session_pair();
+
+    let mut message = alice.encrypt(b"far ahead").unwrap();
+    message[36..HEADER_LENGTH].copy_from_slice(&(MAX_SKIP + 1).to_be_bytes());
+
+    assert_eq!(bob.decrypt(&message).unwrap_err(), EchoError::TooManySkippedMessages);
+    assert_eq!(bob.skipped_message_key_count(), 0);
+}
+
+#[test]
+fn tampered_messages_leave_the_session_unchanged() {
+    let (mut alice, mut bob) = session_pair();
+
+    let message = alice.encrypt(b"hello").unwrap();
+
+    let mut tampered_body = message.clone();
+    *tampered_body.last_mut().unwrap() ^= 0x01;
+    assert_eq!(bob.decrypt(&tampered_body).unwrap_err(), EchoError::DecryptionFailed);
+
+    // The header is authenticated as associated data
+    let mut tampered_header = message.clone();
+    tampered_header[32..36].copy_from_slice(&7u32.to_be_bytes());
+    assert!(bob.decrypt(&tampered_header).is_err());
+
+    assert_eq!(bob.decrypt(&message).unwrap(), b"hello");
+}
+
+#[test]
+fn responder_cannot_send_before_receiving() {
+    let (_, mut bob) = session_pair();
+
+    assert_eq!(bob.encrypt(b"too early").unwrap_err(), EchoError::SessionNotReady);
+}
+
+#[test]
+fn low_order_ratchet_keys_are_rejected() {
+    let mut low_order = [0u8; 32];
+    low_order[0] = 1;
+    let low_order = echo_crypto::PublicKey::from(low_order);
+
+    assert_eq!(
+        RatchetSession::new_initiator(&SHARED_SECRET, &low_order, ASSOCIATED_DATA).err(),
+        Some(EchoError::LowOrderPublicKey)
+    );
+
+    // A message announcing a low order ratchet key fails before it is authenticated and leaves the session untouched
+    let (mut alice, mut bob) = session_pair();
+    let mut message = alice.encrypt(b"hello").unwrap();
+    let genuine = message.clone();
+    message[..32].copy_from_slice(low_order.as_bytes());
+
+    assert_eq!(bob.decrypt(&message).err(), Some(EchoError::LowOrderPublicKey));
+    assert_eq!(bob.decrypt(&genuine).unwrap(), b"hello");
+}
+
+#[test]
+fn header_encrypted_messages_round_trip_across_dh_ratchet_steps() {
+    let (mut alice, mut bob) = header_encrypted_pair();
+    assert!(alice.has_header_encryption() && bob.has_header_encryption());
+    assert_eq!(bob.encrypt(b"too early").unwrap_err(), EchoError::SessionNotReady);
+
+    for round in 0..4 {
+        let to_bob = alice.encrypt(format!("alice {round}").as_bytes()).unwrap();
+        assert_eq!(bob.decrypt(&to_bob).unwrap(), format!("alice {round}").as_bytes());
+
+        let to_alice = bob.encrypt(format!("bob {round}").as_bytes()).unwrap();
+        assert_eq!(alice.decrypt(&to_alice).unwrap(), format!("bob {round}").as_bytes());
+    }
+}
+
+#[test]
+fn header_encryption_hides_ratchet_key_and_counters() {
+    let (mut alice, mut bob) = header_encrypted_pair();
+
+    let first = alice.encrypt(b"same").unwrap();
+    let second = alice.encrypt(b"same").unwrap();
+    assert_eq!(first.len(), ENCRYPTED_HEADER_LENGTH + 4 + 16);
+
+    // Neither the ratchet public key nor the counters appear in the message, and two headers of one chain differ
+    let ratchet_key = alice.ratchet_public_key();
+    assert!(!first.windows(32).any(|window| window == ratchet_key.as_bytes()));
+    assert_ne!(first[..ENCRYPTED_HEADER_LENGTH], second[..ENCRYPTED_HEADER_LENGTH]);
+
+    // A plain session cannot read the header
+    let (_, mut plain_bob) = session_pair();
+    assert!(plain_bob.decrypt(&first).is_err());
+
+    assert_eq!(bob.decrypt(&second).unwrap(), b"same");
+    assert_eq!(bob.decrypt(&first).unwrap(), b"same");
+}
+
+#[test]
+fn header_encrypted_out_of_order_messages_use_skipped_keys_once() {
+    let (mut alice, mut bob) = header_encrypted_pair();
+
+    let first = alice.encrypt(b"first").unwrap();
+    let second = alice.encrypt(b"second").unwrap();
+    let third = alice.encrypt(b"third").unwrap();
+
+    assert_eq!(bob.decrypt(&third).unwrap(), b"third");
+    assert_eq!(bob.skipped_message_key_count(), 2);
+
+    // The next chain opens with the next header key, the late messages with the header key of the skipped chain
+    let reply = bob.encrypt(b"reply").unwrap();
+    alice.decrypt(&reply).unwrap();
+    let next_chain = alice.encrypt(b"next chain").unwrap();
+    assert_eq!(bob.decrypt(&next_chain).unwrap(), b"next chain");
+
+    assert_eq!(bob.decrypt(&second).unwrap(), b"second");
+    assert_eq!(bob.decrypt(&first).unwrap(), b"first");
+    assert_eq!(bob.skipped_message_key_count(), 0);
+
+    assert!(bob.decrypt(&first).is_err());
+}
+
+#[test]
+fn header_encrypted_messages_crossing_in_flight() {
+    let (mut alice, mut bob) = header_encrypted_pair();
+
+    bob.decrypt(&alice.encrypt(b"hello").unwrap()).unwrap();
+    let lost = bob.encrypt(b"lost").unwrap();
+    alice.decrypt(&lost).unwrap();
+
+    // Both sides send before reading the other's latest message, each header opens with HKr or NHKr
+    let from_alice = alice.encrypt(b"one").unwrap();
+    let delayed = bob.encrypt(b"delayed").unwrap();
+    assert_eq!(alice.decrypt(&delayed).unwrap(), b"delayed");
+    assert_eq!(bob.decrypt(&from_alice).unwrap(), b"one");
+    assert_eq!(alice.decrypt(&bob.encrypt(b"two").unwrap()).unwrap(), b"two");
+}
+
+#[test]
+fn header_encrypted_tampering_leaves_the_session_unchanged() {
+    let (mut alice, mut bob) = header_encrypted_pair();
+    let message = alice.encrypt(b"hello").unwrap();
+
+    for index in [0, 20, ENCRYPTED_HEADER_LENGTH - 1, message.len() - 1] {
+        let mut tampered = message.clone();
+        tampered[index] ^= 0x01;
+        assert_eq!(bob.decrypt(&tampered).unwrap_err(), EchoError::DecryptionFailed);
+    }
+
+    assert_eq!(bob.decrypt(&message[..ENCRYPTED_HEADER_LENGTH - 1]).unwrap_err(), EchoError::MalformedMessageHeader);
+    assert_eq!(bob.decrypt(&message).unwrap(), b"hello");
+}
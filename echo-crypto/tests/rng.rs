@@ -0,0 +1,141 @@
+use echo_crypto::aead::generate_key_with;
+use echo_crypto::keys::generate_ed25519_seed_with;
+use echo_crypto::{Argon2Params, EchoError, KeyPair, KeyStore, OneTimePreKeyBatch, RatchetSession, SignedPreKey, Vault};
+use rand_chacha::ChaCha20Rng;
+use std::num::NonZeroU32;
+use rand_chacha::rand_core::{CryptoRng, Error, RngCore, SeedableRng};
+
+fn seeded(seed: u8) -> ChaCha20Rng {
+    ChaCha20Rng::from_seed([seed; 32])
+}
+
+// Generator that always fails, standing in for an unavailable platform generator
+struct FailingRng;
+
+impl RngCore for FailingRng {
+    fn next_u32(&mut self) -> u32 {
+        unimplemented!()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        unimplemented!()
+    }
+
+    fn fill_bytes(&mut self, _dest: &mut [u8]) {
+        unimplemented!()
+    }
+
+    fn try_fill_bytes(&mut self, _dest: &mut [u8]) -> Result<(), Error> {
+        Err(Error::from(NonZeroU32::new(Error::CUSTOM_START).unwrap()))
+    }
+}
+
+impl CryptoRng for FailingRng {}
+
+#[test]
+fn same_seed_generates_same_keys() {
+    let first = KeyPair::generate_with(&mut seeded(1)).unwrap();
+    let second = KeyPair::generate_with(&mut seeded(1)).unwrap();
+    assert_eq!(first.private_key.as_bytes(), second.private_key.as_bytes());
+    assert_eq!(first.public_key, second.public_key);
+
+    assert_eq!(*generate_ed25519_seed_with(&mut seeded(1)).unwrap(), *generate_ed25519_seed_with(&mut seeded(1)).unwrap());
+}
+
+#[test]
+fn different_seeds_generate_different_keys() {
+    let first = KeyPair::generate_with(&mut seeded(1)).unwrap();
+    let second = KeyPair::generate_with(&mut seeded(2)).unwrap();
+    assert_ne!(first.public_key, second.public_key);
+}
+
+#[test]
+fn generated_private_keys_are_clamped() {
+    let key_pair = KeyPair::generate_with(&mut seeded(3)).unwrap();
+    let bytes = key_pair.private_key.as_bytes();
+    assert_eq!(bytes[0] & 7, 0);
+    assert_eq!(bytes[31] & 0xc0, 0x40);
+}
+
+#[test]
+fn signed_prekey_is_reproducible_and_verifies() {
+    let identity = KeyPair::generate_with(&mut seeded(4)).unwrap();
+
+    let first = SignedPreKey::generate_with(7, &identity.private_key, &mut seeded(5)).unwrap();
+    let second = SignedPreKey::generate_with(7, &identity.private_key, &mut seeded(5)).unwrap();
+
+    assert_eq!(first.key_pair.public_key, second.key_pair.public_key);
+    assert_eq!(first.signature, second.signature);
+    assert!(first.verify(&identity.public_key));
+}
+
+#[test]
+fn one_time_prekey_batch_is_reproducible() {
+    let first = OneTimePreKeyBatch::generate_with(10, 3, &mut seeded(6)).unwrap();
+    let second = OneTimePreKeyBatch::generate_with(10, 3, &mut seeded(6)).unwrap();
+    assert_eq!(first.public_bytes(), second.public_bytes());
+
+    let ids: Vec<u32> = first.prekeys().iter().map(|prekey| prekey.id).collect();
+    assert_eq!(ids, [10, 11, 12]);
+    assert_ne!(first.prekeys()[0].public_key, first.prekeys()[1].public_key);
+
+    assert_eq!(OneTimePreKeyBatch::generate_with(0, 0, &mut seeded(6)).err(), Some(EchoError::InvalidPreKeyBatch));
+    assert_eq!(OneTimePreKeyBatch::generate_with(0, 101, &mut seeded(6)).err(), Some(EchoError::InvalidPreKeyBatch));
+}
+
+#[test]
+fn key_store_generators_are_reproducible() {
+    let mut first = KeyStore::new();
+    let mut second = KeyStore::new();
+
+    for key_store in [&mut first, &mut second] {
+        key_store.generate_identity_with(&mut seeded(7)).unwrap();
+        key_store.generate_signed_prekey_with(1, &mut seeded(8)).unwrap();
+    }
+    assert_eq!(
+        first.generate_one_time_prekeys_with(1, 2, &mut seeded(9)).unwrap(),
+        second.generate_one_time_prekeys_with(1, 2, &mut seeded(9)).unwrap()
+    );
+
+    assert_eq!(first.identity_public_key().unwrap(), second.identity_public_key().unwrap());
+    assert_eq!(first.prekey_bundle(1, Some(2)).unwrap().to_bytes(), second.prekey_bundle(1, Some(2)).unwrap().to_bytes());
+
+    let first_ephemeral = first.generate_ephemeral_key_with(&mut seeded(10)).unwrap();
+    let second_ephemeral = second.generate_ephemeral_key_with(&mut seeded(10)).unwrap();
+    assert_eq!(first.public_key(first_ephemeral).unwrap(), second.public_key(second_ephemeral).unwrap());
+
+    assert_eq!(*generate_key_with(&mut seeded(11)).unwrap(), *generate_key_with(&mut seeded(11)).unwrap());
+}
+
+#[test]
+fn ratchet_key_pairs_come_from_the_generator() {
+    let shared_secret = [0x5Au8; 32];
+    let signed_prekey = KeyPair::generate_with(&mut seeded(12)).unwrap();
+
+    let mut alice = RatchetSession::new_initiator_with(&shared_secret, &signed_prekey.public_key, b"AD", &mut seeded(13)).unwrap();
+    let again = RatchetSession::new_initiator_with(&shared_secret, &signed_prekey.public_key, b"AD", &mut seeded(13)).unwrap();
+    assert_eq!(alice.ratchet_public_key(), again.ratchet_public_key());
+    assert_eq!(alice.ratchet_public_key(), KeyPair::generate_with(&mut seeded(13)).unwrap().public_key);
+
+    // The first message starts a DH ratchet step on the responder, its new key pair comes from the generator
+    let mut bob = RatchetSession::new_responder(&shared_secret, signed_prekey, b"AD");
+    let message = alice.encrypt(b"hello").unwrap();
+    assert_eq!(bob.decrypt_with(&message, &mut seeded(14)).unwrap(), b"hello");
+    assert_eq!(bob.ratchet_public_key(), KeyPair::generate_with(&mut seeded(14)).unwrap().public_key);
+}
+
+#[test]
+fn failing_generator_is_reported() {
+    assert_eq!(KeyPair::generate_with(&mut FailingRng).err(), Some(EchoError::RandomnessUnavailable));
+    assert_eq!(generate_ed25519_seed_with(&mut FailingRng).err(), Some(EchoError::RandomnessUnavailable));
+    assert_eq!(generate_key_with(&mut FailingRng).err(), Some(EchoError::RandomnessUnavailable));
+    assert_eq!(KeyStore::new().generate_identity_with(&mut FailingRng).err(), Some(EchoError::RandomnessUnavailable));
+
+    let params = Argon2Params { memory_kib: 256, iterations: 1, parallelism: 1 };
+    assert_eq!(Vault::create_with(b"password", params, KeyStore::new(), &mut FailingRng).err(), Some(EchoError::RandomnessUnavailable));
+}
+
+#[test]
+fn platform_generator_produces_distinct_keys() {
+    assert_ne!(KeyPair::generate().unwrap().public_key, KeyPair::generate().unwrap().public_key);
+}
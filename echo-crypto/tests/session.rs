@@ -0,0 +1,129 @@
+use echo_crypto::session::SESSION_RECORD_VERSION;
+use echo_crypto::{EchoError, KeyPair, PublicKey, RatchetSession, SessionRecord};
+
+const SHARED_SECRET: [u8; 32] = [0x5Au8; 32];
+const ASSOCIATED_DATA: &[u8] = b"IK_A || IK_B";
+
+fn record_pair() -> (SessionRecord, SessionRecord) {
+    let signed_prekey = KeyPair::from_random_bytes(&[0x24u8; 32]).unwrap();
+    let alice_identity = PublicKey::from([0x0Au8; 32]);
+    let bob_identity = PublicKey::from([0x0Bu8; 32]);
+
+    let alice = RatchetSession::new_initiator(&SHARED_SECRET, &signed_prekey.public_key, ASSOCIATED_DATA).unwrap();
+    let bob = RatchetSession::new_responder(&SHARED_SECRET, signed_prekey, ASSOCIATED_DATA);
+
+    (SessionRecord::new(bob_identity, alice), SessionRecord::new(alice_identity, bob))
+}
+
+fn reload(record: &SessionRecord) -> SessionRecord {
+    SessionRecord::from_bytes(&record.to_bytes()).unwrap()
+}
+
+#[test]
+fn reloaded_records_continue_the_conversation() {
+    let (mut alice, mut bob) = record_pair();
+
+    let first = alice.encrypt(b"one").unwrap();
+    let skipped = alice.encrypt(b"two").unwrap();
+    let third = alice.encrypt(b"three").unwrap();
+    assert_eq!(bob.decrypt(&first).unwrap(), b"one");
+    assert_eq!(bob.decrypt(&third).unwrap(), b"three");
+
+    // Every piece of state survives a save and load: chains, counters and the skipped key of "two"
+    let mut alice = reload(&alice);
+    let mut bob = reload(&bob);
+    assert_eq!(bob.session().skipped_message_key_count(), 1);
+    assert_eq!(bob.remote_identity(), &PublicKey::from([0x0Au8; 32]));
+
+    assert_eq!(bob.decrypt(&skipped).unwrap(), b"two");
+    let reply = bob.encrypt(b"four").unwrap();
+    assert_eq!(alice.decrypt(&reply).unwrap(), b"four");
+
+    let mut bob = reload(&bob);
+    let next = alice.encrypt(b"five").unwrap();
+    assert_eq!(bob.decrypt(&next).unwrap(), b"five");
+}
+
+#[test]
+fn serialization_is_deterministic() {
+    let (mut alice, bob) = record_pair();
+    alice.encrypt(b"hello").unwrap();
+
+    assert_eq!(*alice.to_bytes(), *reload(&alice).to_bytes());
+    assert_eq!(*bob.to_bytes(), *reload(&bob).to_bytes());
+    assert_eq!(alice.to_bytes()[0], SESSION_RECORD_VERSION);
+}
+
+#[test]
+fn failed_decrypt_leaves_the_saved_state_unchanged() {
+    let (mut alice, mut bob) = record_pair();
+    let mut message = alice.encrypt(b"hello").unwrap();
+    let before = bob.to_bytes();
+
+    let last = message.len() - 1;
+    message[last] ^= 1;
+    assert_eq!(bob.decrypt(&message), Err(EchoError::DecryptionFailed));
+    assert_eq!(*bob.to_bytes(), *before);
+}
+
+// Bob's record written at version 1 after receiving "one" and "three", "two" is still pending
+const VERSION_1_RECORD: &str = "010a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0ab0e2322313bec30f7d71477f7c3eb029a88023fe4db0a9b908cf883aa8c4655a01ca2523ef3e8d704757503e36367c6cf7d4f40e02ec29106caed44416a9b9fa0b7305f3a1bcbf851102abffadf90015cab16373c3d286f8367913a0b81f439ea3011050e0e0818c00cb032baa739e480a468b410daf054793ca4e00552dbe6c948e016104a9d7ed4ff637bd97554b9d6d9db72baebd7c749d06551bad26ed7067ac3500000000000000030000000000000001ca2523ef3e8d704757503e36367c6cf7d4f40e02ec29106caed44416a9b9fa0b000000016ddc013151a83af1af8c265e8ea53ed3a4a2530a7d823bad5355f11d5c5e6df10000000c494b5f41207c7c20494b5f42";
+const VERSION_1_PENDING_TWO: &str = "ca2523ef3e8d704757503e36367c6cf7d4f40e02ec29106caed44416a9b9fa0b0000000000000001fedc2d8e5a3cc8cf21495df439c4963eb3b718";
+const VERSION_1_NEXT_FOUR: &str = "ca2523ef3e8d704757503e36367c6cf7d4f40e02ec29106caed44416a9b9fa0b000000000000000311ca559fdb257f9d678b54783feb0f5b86bb08e5";
+
+#[test]
+fn version_1_records_are_migrated() {
+    let mut bob = SessionRecord::from_bytes(&hex::decode(VERSION_1_RECORD).unwrap()).unwrap();
+    assert!(!bob.session().has_header_encryption());
+    assert_eq!(bob.session().skipped_message_key_count(), 1);
+
+    assert_eq!(bob.decrypt(&hex::decode(VERSION_1_PENDING_TWO).unwrap()).unwrap(), b"two");
+    assert_eq!(bob.decrypt(&hex::decode(VERSION_1_NEXT_FOUR).unwrap()).unwrap(), b"four");
+    assert_eq!(bob.to_bytes()[0], SESSION_RECORD_VERSION);
+}
+
+#[test]
+fn header_encrypted_records_continue_the_conversation() {
+    let signed_prekey = KeyPair::from_random_bytes(&[0x24u8; 32]).unwrap();
+    let alice = RatchetSession::new_initiator_with_header_encryption(&SHARED_SECRET, &signed_prekey.public_key, ASSOCIATED_DATA).unwrap();
+    let bob = RatchetSession::new_responder_with_header_encryption(&SHARED_SECRET, signed_prekey, ASSOCIATED_DATA);
+    let mut alice = SessionRecord::new(PublicKey::from([0x0Bu8; 32]), alice);
+    let bob = SessionRecord::new(PublicKey::from([0x0Au8; 32]), bob);
+
+    let first = alice.encrypt(b"one").unwrap();
+    let skipped = alice.encrypt(b"two").unwrap();
+    let mut bob_reloaded = reload(&bob);
+    assert!(bob_reloaded.session().has_header_encryption());
+    assert_eq!(bob_reloaded.decrypt(&first).unwrap(), b"one");
+
+    let mut bob = reload(&bob_reloaded);
+    assert_eq!(*bob.to_bytes(), *bob_reloaded.to_bytes());
+    assert_eq!(bob.decrypt(&skipped).unwrap(), b"two");
+
+    let reply = reload(&bob).encrypt(b"three").unwrap();
+    assert_eq!(reload(&alice).decrypt(&reply).unwrap(), b"three");
+}
+
+#[test]
+fn malformed_and_future_records_are_rejected() {
+    let (alice, _) = record_pair();
+    let bytes = alice.to_bytes();
+
+    assert_eq!(SessionRecord::from_bytes(&[]).err(), Some(EchoError::MalformedSessionRecord));
+    assert_eq!(SessionRecord::from_bytes(&bytes[..bytes.len() - 1]).err(), Some(EchoError::MalformedSessionRecord));
+    assert_eq!(SessionRecord::from_bytes(&[&bytes[..], &[0]].concat()).err(), Some(EchoError::MalformedSessionRecord));
+
+    let mut future = bytes.to_vec();
+    future[0] = SESSION_RECORD_VERSION + 1;
+    assert_eq!(SessionRecord::from_bytes(&future).err(), Some(EchoError::UnsupportedVersion));
+
+    // The DHR presence flag must be 0 or 1
+    let mut bad_flag = bytes.to_vec();
+    bad_flag[1 + 32 + 32] = 2;
+    assert_eq!(SessionRecord::from_bytes(&bad_flag).err(), Some(EchoError::MalformedSessionRecord));
+
+    // So must the header encryption flag, the last byte of a session without it
+    let mut bad_flag = bytes.to_vec();
+    *bad_flag.last_mut().unwrap() = 2;
+    assert_eq!(SessionRecord::from_bytes(&bad_flag).err(), Some(EchoError::MalformedSessionRecord));
+}
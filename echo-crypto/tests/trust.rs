@@ -0,0 +1,67 @@
+use echo_crypto::trust::TRUST_STORE_VERSION;
+use echo_crypto::{EchoError, PublicKey, TrustStatus, TrustStore, TrustedIdentity};
+
+fn key(byte: u8) -> PublicKey {
+    PublicKey::from([byte; 32])
+}
+
+fn status(result: Result<TrustedIdentity, EchoError>) -> Result<TrustStatus, EchoError> {
+    result.map(|trusted| trusted.status())
+}
+
+#[test]
+fn first_key_is_remembered_and_then_trusted() {
+    let mut store = TrustStore::new();
+
+    assert_eq!(store.check("bob", &key(1)), TrustStatus::Unknown);
+    assert_eq!(status(store.ensure_trusted("bob", &key(1))), Ok(TrustStatus::Unknown));
+    assert_eq!(store.check("bob", &key(1)), TrustStatus::Trusted);
+    assert_eq!(status(store.ensure_trusted("bob", &key(1))), Ok(TrustStatus::Trusted));
+    assert_eq!(store.ensure_trusted("bob", &key(1)).unwrap().identity_key(), &key(1));
+    assert_eq!(store.identity_key("bob"), Some(&key(1)));
+
+    // Contacts are tracked independently
+    assert_eq!(store.check("carol", &key(1)), TrustStatus::Unknown);
+}
+
+#[test]
+fn changed_key_is_refused_until_approved() {
+    let mut store = TrustStore::new();
+    store.ensure_trusted("bob", &key(1)).unwrap();
+
+    assert_eq!(store.check("bob", &key(2)), TrustStatus::Changed);
+    assert_eq!(store.ensure_trusted("bob", &key(2)), Err(EchoError::IdentityKeyChanged));
+
+    // Refusing does not replace the remembered key
+    assert_eq!(store.identity_key("bob"), Some(&key(1)));
+    assert_eq!(store.ensure_trusted("bob", &key(2)), Err(EchoError::IdentityKeyChanged));
+
+    let approved = store.approve("bob", &key(2));
+    assert_eq!((approved.identity_key(), approved.status()), (&key(2), TrustStatus::Trusted));
+    assert_eq!(status(store.ensure_trusted("bob", &key(2))), Ok(TrustStatus::Trusted));
+    assert_eq!(store.check("bob", &key(1)), TrustStatus::Changed);
+}
+
+#[test]
+fn forgotten_contacts_start_over() {
+    let mut store = TrustStore::new();
+    store.ensure_trusted("bob", &key(1)).unwrap();
+
+    assert!(store.forget("bob"));
+    assert!(!store.forget("bob"));
+    assert_eq!(status(store.ensure_trusted("bob", &key(2))), Ok(TrustStatus::Unknown));
+}
+
+#[test]
+fn store_round_trips() {
+    let mut store = TrustStore::new();
+    store.ensure_trusted("bob", &key(1)).unwrap();
+    store.ensure_trusted("carol", &key(2)).unwrap();
+
+    let bytes = store.to_bytes();
+    assert_eq!(bytes[0], TRUST_STORE_VERSION);
+    assert_eq!(TrustStore::from_bytes(&bytes), Ok(store));
+
+    assert_eq!(TrustStore::from_bytes(&bytes[..bytes.len() - 1]), Err(EchoError::MalformedTrustStore));
+    assert_eq!(TrustStore::from_bytes(&[TRUST_STORE_VERSION + 1, 0, 0, 0, 0]), Err(EchoError::UnsupportedVersion));
+}
@@ -0,0 +1,155 @@
+use echo_crypto::vault::{MAX_ITERATIONS, MAX_MEMORY_KIB, MAX_PARALLELISM, VAULT_VERSION};
+use echo_crypto::{Argon2Params, EchoError, KeyPair, KeyStore, PublicKey, RatchetSession, SessionRecord, TrustStatus, Vault};
+
+// Cheap parameters so the tests stay fast, real vaults use Argon2Params::default()
+const TEST_PARAMS: Argon2Params = Argon2Params {
+    memory_kib: 256,
+    iterations: 1,
+    parallelism: 1,
+};
+
+fn vault(password: &[u8]) -> Vault {
+    let mut key_store = KeyStore::new();
+    key_store.generate_identity().unwrap();
+    key_store.generate_signed_prekey(1).unwrap();
+    key_store.generate_one_time_prekeys(1, 2).unwrap();
+
+    let mut vault = Vault::create(password, TEST_PARAMS, key_store).unwrap();
+    vault.set_session("bob", b"serialized session with bob".to_vec());
+    vault
+}
+
+#[test]
+fn vault_round_trips_with_the_password() {
+    let vault = vault(b"correct horse");
+    let bytes = vault.to_bytes().unwrap();
+    assert_eq!(bytes[0], VAULT_VERSION);
+
+    let unlocked = Vault::unlock(&bytes, b"correct horse").unwrap();
+    assert_eq!(unlocked.params(), TEST_PARAMS);
+    assert_eq!(unlocked.key_store.identity_public_key(), vault.key_store.identity_public_key());
+    assert_eq!(unlocked.key_store.prekey_bundle(1, Some(2)), vault.key_store.prekey_bundle(1, Some(2)));
+    assert_eq!(unlocked.session("bob"), Some(&b"serialized session with bob"[..]));
+    assert_eq!(unlocked.session_ids().collect::<Vec<_>>(), ["bob"]);
+}
+
+#[test]
+fn wrong_password_is_reported() {
+    let bytes = vault(b"correct horse").to_bytes().unwrap();
+    assert_eq!(Vault::unlock(&bytes, b"battery staple").err(), Some(EchoError::WrongPassword));
+}
+
+#[test]
+fn change_password_rewraps_only_the_dek() {
+    let mut vault = vault(b"old password");
+    let before = vault.to_bytes().unwrap();
+
+    assert_eq!(vault.change_password(b"not it", b"new password", TEST_PARAMS), Err(EchoError::WrongPassword));
+    vault.change_password(b"old password", b"new password", TEST_PARAMS).unwrap();
+    let after = vault.to_bytes().unwrap();
+
+    assert_eq!(Vault::unlock(&after, b"old password").err(), Some(EchoError::WrongPassword));
+    let unlocked = Vault::unlock(&after, b"new password").unwrap();
+    assert_eq!(unlocked.session("bob"), vault.session("bob"));
+
+    // The DEK is unchanged, so the old payload still opens under the new header
+    let header_length = 1 + 12 + 16 + 61;
+    let spliced = [&after[..header_length], &before[header_length..]].concat();
+    assert!(Vault::unlock(&spliced, b"new password").is_ok());
+}
+
+#[test]
+fn tampering_is_detected() {
+    let bytes = vault(b"pw").to_bytes().unwrap();
+
+    // Changing the stored parameters changes the KEK and the DEK envelope AAD
+    let mut params_changed = bytes.clone();
+    params_changed[4] ^= 1;
+    assert_eq!(Vault::unlock(&params_changed, b"pw").err(), Some(EchoError::WrongPassword));
+
+    let mut payload_changed = bytes.clone();
+    let last = payload_changed.len() - 1;
+    payload_changed[last] ^= 1;
+    assert_eq!(Vault::unlock(&payload_changed, b"pw").err(), Some(EchoError::DecryptionFailed));
+
+    assert_eq!(Vault::unlock(&bytes[..40], b"pw").err(), Some(EchoError::MalformedVault));
+
+    let mut future_version = bytes.clone();
+    future_version[0] = VAULT_VERSION + 1;
+    assert_eq!(Vault::unlock(&future_version, b"pw").err(), Some(EchoError::UnsupportedVersion));
+}
+
+#[test]
+fn unreasonable_parameters_are_rejected() {
+    let too_much_memory = Argon2Params { memory_kib: MAX_MEMORY_KIB + 1, ..TEST_PARAMS };
+    assert_eq!(Vault::create(b"pw", too_much_memory, KeyStore::new()).err(), Some(EchoError::InvalidKdfParams));
+
+    let no_iterations = Argon2Params { iterations: 0, ..TEST_PARAMS };
+    assert_eq!(Vault::create(b"pw", no_iterations, KeyStore::new()).err(), Some(EchoError::InvalidKdfParams));
+
+    let too_many_iterations = Argon2Params { iterations: MAX_ITERATIONS + 1, ..TEST_PARAMS };
+    assert_eq!(Vault::create(b"pw", too_many_iterations, KeyStore::new()).err(), Some(EchoError::InvalidKdfParams));
+
+    let too_many_lanes = Argon2Params { parallelism: MAX_PARALLELISM + 1, ..TEST_PARAMS };
+    assert_eq!(Vault::create(b"pw", too_many_lanes, KeyStore::new()).err(), Some(EchoError::InvalidKdfParams));
+}
+
+// The KDF header is not authenticated before Argon2 runs, out of range costs must fail without running it
+#[test]
+fn tampered_kdf_parameters_are_rejected_before_argon2() {
+    let bytes = vault(b"pw").to_bytes().unwrap();
+
+    // M_COST, T_COST and P_COST follow the version byte
+    for (offset, cost) in [(1, MAX_MEMORY_KIB + 1), (1, u32::MAX), (5, MAX_ITERATIONS + 1), (5, u32::MAX), (9, MAX_PARALLELISM + 1), (9, u32::MAX)] {
+        let mut tampered = bytes.clone();
+        tampered[offset..offset + 4].copy_from_slice(&cost.to_be_bytes());
+        assert_eq!(Vault::unlock(&tampered, b"pw").err(), Some(EchoError::InvalidKdfParams), "offset {offset}, cost {cost}");
+    }
+
+    assert!(Vault::unlock(&bytes, b"pw").is_ok());
+}
+
+#[test]
+fn session_records_are_stored_and_decoded_inside_the_vault() {
+    let signed_prekey = KeyPair::from_random_bytes(&[0x24u8; 32]).unwrap();
+    let alice = RatchetSession::new_initiator(&[0x5Au8; 32], &signed_prekey.public_key, b"AD").unwrap();
+    let mut bob = SessionRecord::new(
+        PublicKey::from([0x0Au8; 32]),
+        RatchetSession::new_responder(&[0x5Au8; 32], signed_prekey, b"AD"),
+    );
+    let mut alice = SessionRecord::new(PublicKey::from([0x0Bu8; 32]), alice);
+
+    let mut vault = vault(b"pw");
+    vault.set_session_record("alice", &bob);
+    let unlocked = Vault::unlock(&vault.to_bytes().unwrap(), b"pw").unwrap();
+
+    let mut restored = unlocked.session_record("alice").unwrap().unwrap();
+    assert_eq!(restored.to_bytes(), bob.to_bytes());
+    let message = alice.encrypt(b"hello").unwrap();
+    assert_eq!(restored.decrypt(&message).unwrap(), bob.decrypt(&message).unwrap());
+
+    assert!(unlocked.session_record("carol").unwrap().is_none());
+    // "bob" holds placeholder bytes, not a record
+    assert_eq!(unlocked.session_record("bob").err(), Some(EchoError::UnsupportedVersion));
+}
+
+// A version 1 vault (before the trust store) written with TEST_PARAMS and the password "pw"
+const VERSION_1_VAULT: &str = "01000001000000000100000001ffcda3998393e13272a2eda344c93cb001ac5db3799fcaf456a6721fc965092c5b8171076183dc94716a101ea80a73c43af7df62e0a4b9098f4c5c671427696f33c2e5da629144bd493a76a7c001d2eb6c7e05a0a30b11478becc56c64e66f934bde7b76035af30caef8fdead77d3eda331e020074d9bf5908cceae727ac0c745ef8fd4afa706131801f1a0b4fa6cfb3f80ae4d7915ac208ae6234e7b4100477d0ae88483be3a0ada4e124cb25d5d29945da6fe3b8";
+const VERSION_1_IDENTITY: &str = "23f9489953b4640afa05dbcfead1547a1409d0812d388b4524a02edb51fef621";
+
+#[test]
+fn version_1_vaults_are_upgraded_on_unlock() {
+    let mut vault = Vault::unlock(&hex::decode(VERSION_1_VAULT).unwrap(), b"pw").unwrap();
+    assert_eq!(vault.key_store.identity_public_key().unwrap().as_bytes().to_vec(), hex::decode(VERSION_1_IDENTITY).unwrap());
+    assert_eq!(vault.session("bob"), Some(&b"record"[..]));
+    assert_eq!(vault.trust_store.identity_key("bob"), None);
+
+    let bob = PublicKey::from([0xB0u8; 32]);
+    vault.trust_store.approve("bob", &bob);
+
+    let bytes = vault.to_bytes().unwrap();
+    assert_eq!(bytes[0], VAULT_VERSION);
+    let reopened = Vault::unlock(&bytes, b"pw").unwrap();
+    assert_eq!(reopened.trust_store.check("bob", &bob), TrustStatus::Trusted);
+    assert_eq!(reopened.session("bob"), Some(&b"record"[..]));
+}
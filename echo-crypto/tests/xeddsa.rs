@@ -0,0 +1,392 @@
+use curve25519_dalek::constants::{ED25519_BASEPOINT_POINT, EIGHT_TORSION};
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::traits::Identity;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::Scalar;
+use echo_crypto::keys::{clamp, ed25519_public_key_from_seed};
+use echo_crypto::xeddsa::{
+    calculate_key_pair, challenge_hash, decode_point_strict, deterministic_nonce, expand_private_key, nonce_point, reduce_hash_mod_l,
+    sign, signature_scalar, verify, verify_signature, verify_signature_strict, verify_strict,
+};
+use echo_crypto::{PrivateKey, PublicKey};
+use sha2::{Digest, Sha512};
+
+fn bytes(hex_str: &str) -> Vec<u8> {
+    hex::decode(hex_str).unwrap()
+}
+
+// k = SHA512(R || A || M) mod L
+fn challenge(r: &[u8; 32], a: &[u8; 32], message: &[u8]) -> Scalar {
+    Scalar::from_bytes_mod_order_wide(&Sha512::new().chain_update(r).chain_update(a).chain_update(message).finalize().into())
+}
+
+fn signature_bytes(r: &[u8; 32], s: &Scalar) -> [u8; 64] {
+    let mut signature = [0u8; 64];
+    signature[..32].copy_from_slice(r);
+    signature[32..].copy_from_slice(s.as_bytes());
+    signature
+}
+
+// This function returns the Ed25519 scalar a and the public key A = aB of an RFC 8032 seed
+fn ed25519_key(seed: &[u8]) -> (Scalar, [u8; 32]) {
+    let expanded = expand_private_key(seed);
+    let a = Scalar::from_bytes_mod_order(expanded[..32].try_into().unwrap());
+    (a, (a * ED25519_BASEPOINT_POINT).compress().to_bytes())
+}
+
+// Encoding of the identity point (x = 0, y = 1)
+const IDENTITY: [u8; 32] = {
+    let mut identity = [0u8; 32];
+    identity[0] = 1;
+    identity
+};
+
+// RFC 8032 section 7.1, test vectors 1 to 3 (secret key, public key, message, signature)
+const RFC8032_VECTORS: [(&str, &str, &str, &str); 3] = [
+    (
+        "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+        "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+        "",
+        "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+    ),
+    (
+        "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+        "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+        "72",
+        "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+    ),
+    (
+        "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+        "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+        "af82",
+        "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+    ),
+];
+
+// Reduction of a 64 byte hash mod L = 2^252 + 27742317777372353535851937790883648493
+#[test]
+fn reduce_hash_mod_l_matches_big_integer_reduction() {
+    assert_eq!(reduce_hash_mod_l(&[0u8; 64]), Scalar::ZERO);
+
+    // L itself (little endian, zero extended) reduces to zero and L + 1 to one
+    let mut l = [0u8; 64];
+    l[..32].copy_from_slice(&bytes("edd3f55c1a631258d69cf7a2def9de1400000000000000000000000000000010"));
+    assert_eq!(reduce_hash_mod_l(&l), Scalar::ZERO);
+    l[0] += 1;
+    assert_eq!(reduce_hash_mod_l(&l), Scalar::ONE);
+
+    // (2^512 - 1) mod L
+    assert_eq!(
+        reduce_hash_mod_l(&[0xFF; 64]).to_bytes().to_vec(),
+        bytes("000f9c44e31106a447938568a71b0ed065bef517d273ecce3d9a307c1b419903")
+    );
+}
+
+#[test]
+fn ed25519_rfc8032_public_keys() {
+    for (secret, public, _, _) in RFC8032_VECTORS {
+        assert_eq!(ed25519_public_key_from_seed(&bytes(secret)).to_vec(), bytes(public));
+    }
+}
+
+// The step by step signing functions are plain Ed25519 over the seed, so they must reproduce RFC 8032
+#[test]
+fn ed25519_rfc8032_signatures_from_step_functions() {
+    for (secret, public, message, signature) in RFC8032_VECTORS {
+        let secret = bytes(secret);
+        let message = bytes(message);
+
+        let expanded = expand_private_key(&secret);
+        let r = deterministic_nonce(&expanded[32..], &message);
+        let r_point = nonce_point(&r);
+        let k = challenge_hash(&r_point, &bytes(public).try_into().unwrap(), &message);
+        let s = signature_scalar(&r, &k, &expanded[..32].try_into().unwrap());
+
+        assert_eq!([r_point, s].concat(), bytes(signature));
+    }
+}
+
+#[test]
+fn ed25519_rfc8032_verification() {
+    for (_, public, message, signature) in RFC8032_VECTORS {
+        let public = bytes(public);
+        let mut message = bytes(message);
+        let signature = bytes(signature);
+
+        assert!(verify_signature(&signature, &message, &public));
+
+        message.push(0x00);
+        assert!(!verify_signature(&signature, &message, &public));
+    }
+}
+
+// XEdDSA verification is Ed25519 verification after mapping u to the Edwards point with sign bit 0
+// All three RFC 8032 public keys have sign bit 0, so their signatures must verify from the Montgomery form
+#[test]
+fn xeddsa_verify_rfc8032_vectors_from_montgomery_keys() {
+    for (_, public, message, signature) in RFC8032_VECTORS {
+        let public: [u8; 32] = bytes(public).try_into().unwrap();
+        assert_eq!(public[31] >> 7, 0);
+
+        let u = PublicKey::from(CompressedEdwardsY(public).decompress().unwrap().to_montgomery().to_bytes());
+        let signature: [u8; 64] = bytes(signature).try_into().unwrap();
+
+        assert!(verify(&u, &bytes(message), &signature));
+        assert!(!verify(&u, b"another message", &signature));
+    }
+}
+
+// libsignal curve tests, test_signature: Alice's identity key pair and her signature over the serialized
+// ephemeral public key (0x05 type byte || u)
+const LIBSIGNAL_IDENTITY_PRIVATE: &str = "c097248412e58bf05df487968205132794178e367637f5818f81e0e6ce73e865";
+const LIBSIGNAL_IDENTITY_PUBLIC: &str = "ab7e717d4a163b7d9a1d8071dfe9dcf8cdcd1cea3339b6356be84d887e322c64";
+const LIBSIGNAL_MESSAGE: &str = "05edce9d9c415ca78cb7252e72c2c4a554d3eb29485a0e1d503118d1a82d99fb4a";
+const LIBSIGNAL_SIGNATURE: &str =
+    "5de88ca9a89b4a115da79109c67c9c7464a3e4180274f1cb8c63c2984e286dfbede82deb9dcd9fae0bfbb821569b3d9001bd8130cd11d486cef047bd60b86e88";
+
+#[test]
+fn xeddsa_verify_libsignal_vector() {
+    let private_key = PrivateKey::from_bytes(&bytes(LIBSIGNAL_IDENTITY_PRIVATE)).unwrap();
+    let public_key = PublicKey::from_bytes(&bytes(LIBSIGNAL_IDENTITY_PUBLIC)).unwrap();
+    assert_eq!(private_key.public_key(), public_key);
+
+    let message = bytes(LIBSIGNAL_MESSAGE);
+    let signature: [u8; 64] = bytes(LIBSIGNAL_SIGNATURE).try_into().unwrap();
+
+    // libsignal stores the sign bit of its Edwards key in the top bit of s, here it is set
+    assert_eq!(signature[63] >> 7, 1);
+    assert!(verify(&public_key, &message, &signature));
+    assert!(verify_strict(&public_key, &message, &signature));
+
+    let mut other_message = message.clone();
+    other_message[1] ^= 0x01;
+    assert!(!verify(&public_key, &other_message, &signature));
+
+    let mut other_sign_bit = signature;
+    other_sign_bit[63] &= 0x7F;
+    assert!(!verify(&public_key, &message, &other_sign_bit));
+
+    // A signature made here over the same message verifies with the same key
+    let own = sign(&private_key, &message, &[0x03u8; 64]);
+    assert_eq!(own[63] >> 7, 0);
+    assert!(verify(&public_key, &message, &own));
+}
+
+#[test]
+fn xeddsa_sign_and_verify_both_sign_bits() {
+    let mut seen_sign_bits = [false; 2];
+
+    for seed in 0u8..32 {
+        let mut private_key = [seed; 32];
+        private_key[1] = seed.wrapping_mul(101);
+        clamp(&mut private_key);
+
+        let edwards_sign_bit = (Scalar::from_bytes_mod_order(private_key) * ED25519_BASEPOINT_POINT).compress().to_bytes()[31] >> 7;
+        seen_sign_bits[edwards_sign_bit as usize] = true;
+
+        let private_key = PrivateKey::from_bytes(&private_key).unwrap();
+        let u = private_key.public_key();
+        let (public_key, _) = calculate_key_pair(&private_key);
+        assert_eq!(public_key[31] >> 7, 0);
+        assert_eq!(MontgomeryPoint(u.to_bytes()).to_edwards(0).unwrap().compress().to_bytes(), public_key);
+
+        let first = sign(&private_key, b"signed prekey", &[0x01u8; 64]);
+        let second = sign(&private_key, b"signed prekey", &[0x02u8; 64]);
+        assert_ne!(first, second);
+
+        for signature in [&first, &second] {
+            assert!(verify(&u, b"signed prekey", signature));
+            assert!(verify_signature(signature, b"signed prekey", &public_key));
+            assert!(!verify(&u, b"signed prekeY", signature));
+        }
+    }
+
+    assert_eq!(seen_sign_bits, [true, true]);
+}
+
+#[test]
+fn xeddsa_verify_rejects_malformed_input() {
+    let private_key = PrivateKey::from_bytes(&[0x55u8; 32]).unwrap();
+    let signature = sign(&private_key, b"message", &[0u8; 64]);
+
+    // u >= p is rejected before mapping to Edwards form
+    let mut non_canonical_u = [0xFFu8; 32];
+    non_canonical_u[31] = 0x7F;
+    assert!(!verify(&PublicKey::from(non_canonical_u), b"message", &signature));
+
+    // A non canonical s is rejected
+    let mut large_s = signature;
+    large_s[63] |= 0xF0;
+    assert!(!verify(&private_key.public_key(), b"message", &large_s));
+}
+
+#[test]
+fn strict_verification_accepts_honest_signatures() {
+    for (_, public, message, signature) in RFC8032_VECTORS {
+        assert!(verify_signature_strict(&bytes(signature), &bytes(message), &bytes(public)));
+    }
+
+    for seed in 0u8..16 {
+        let private_key = PrivateKey::from_bytes(&[seed; 32]).unwrap();
+        let (public_key, _) = calculate_key_pair(&private_key);
+        let signature = sign(&private_key, b"signed prekey", &[seed; 64]);
+
+        assert!(verify_strict(&private_key.public_key(), b"signed prekey", &signature));
+        assert!(verify_signature_strict(&signature, b"signed prekey", &public_key));
+        assert!(!verify_strict(&private_key.public_key(), b"signed prekeY", &signature));
+    }
+}
+
+// With A = identity, R = identity and s = 0 satisfy sB == R + kA for every message
+#[test]
+fn strict_verification_rejects_identity_public_key() {
+    let signature = signature_bytes(&IDENTITY, &Scalar::ZERO);
+
+    for message in [&b""[..], b"any message", b"another message"] {
+        assert!(verify_signature(&signature, message, &IDENTITY));
+        assert!(!verify_signature_strict(&signature, message, &IDENTITY));
+    }
+}
+
+// Every small order point is rejected by the strict decoder, large order points are accepted
+#[test]
+fn strict_decoding_rejects_small_order_points() {
+    for torsion in EIGHT_TORSION {
+        assert!(decode_point_strict(&torsion.compress().to_bytes()).is_none());
+    }
+
+    let (_, public_key) = ed25519_key(&[0x42; 32]);
+    assert!(decode_point_strict(&public_key).is_some());
+
+    // A + T has a torsion component but is not of small order, it is left to the cofactorless equation
+    let mixed = CompressedEdwardsY(public_key).decompress().unwrap() + EIGHT_TORSION[1];
+    assert!(decode_point_strict(&mixed.compress().to_bytes()).is_some());
+}
+
+// y >= p and negative zero (x = 0 with the sign bit set) are non-canonical encodings of valid points
+#[test]
+fn strict_decoding_rejects_non_canonical_encodings() {
+    // y = p + 1, which decodes to the identity
+    let mut identity_plus_p = [0xFFu8; 32];
+    identity_plus_p[0] = 0xEE;
+    identity_plus_p[31] = 0x7F;
+    assert!(CompressedEdwardsY(identity_plus_p).decompress().is_some());
+    assert!(decode_point_strict(&identity_plus_p).is_none());
+
+    let mut negative_zero = IDENTITY;
+    negative_zero[31] |= 0x80;
+    assert!(CompressedEdwardsY(negative_zero).decompress().is_some());
+    assert!(decode_point_strict(&negative_zero).is_none());
+
+    // y + p for every small y that is on the curve with a point of large order
+    let mut checked = 0;
+    for y in 2u8..19 {
+        let mut canonical = [0u8; 32];
+        canonical[0] = y;
+        let Some(point) = CompressedEdwardsY(canonical).decompress() else { continue };
+        if point.is_small_order() {
+            continue;
+        }
+
+        let mut non_canonical = [0xFFu8; 32];
+        non_canonical[0] = 0xED + y;
+        non_canonical[31] = 0x7F;
+        assert_eq!(CompressedEdwardsY(non_canonical).decompress(), Some(point));
+        assert!(decode_point_strict(&canonical).is_some());
+        assert!(decode_point_strict(&non_canonical).is_none());
+        checked += 1;
+    }
+    assert!(checked > 0);
+}
+
+// The signer picks r = 0, so R is the identity and s = ka satisfies the equation
+#[test]
+fn strict_verification_rejects_small_order_nonce_point() {
+    let (a, public_key) = ed25519_key(&[0x42; 32]);
+
+    // Canonical identity and y = p + 1
+    let mut identity_plus_p = [0xFFu8; 32];
+    identity_plus_p[0] = 0xEE;
+    identity_plus_p[31] = 0x7F;
+
+    for r in [IDENTITY, identity_plus_p] {
+        let signature = signature_bytes(&r, &(challenge(&r, &public_key, b"message") * a));
+        assert!(verify_signature(&signature, b"message", &public_key));
+        assert!(!verify_signature_strict(&signature, b"message", &public_key));
+    }
+}
+
+// s and s + L are congruent mod L, only the canonical s may be accepted
+#[test]
+fn strict_verification_rejects_non_canonical_scalar() {
+    let (_, public, message, signature) = RFC8032_VECTORS[1];
+    let signature = bytes(signature);
+
+    let l = bytes("edd3f55c1a631258d69cf7a2def9de1400000000000000000000000000000010");
+    let mut malleated = signature.clone();
+    let mut carry = 0u16;
+    for i in 0..32 {
+        let sum = malleated[32 + i] as u16 + l[i] as u16 + carry;
+        malleated[32 + i] = sum as u8;
+        carry = sum >> 8;
+    }
+    assert_eq!(carry, 0);
+
+    assert!(verify_signature_strict(&signature, &bytes(message), &bytes(public)));
+    assert!(!verify_signature_strict(&malleated, &bytes(message), &bytes(public)));
+    assert!(!verify_signature(&malleated, &bytes(message), &bytes(public)));
+}
+
+// A signature under A' = A + T (T of order 8) only satisfies the cofactored equation 8R == 8(sB - kA')
+// The strict policy is cofactorless and rejects it
+#[test]
+fn strict_verification_is_cofactorless() {
+    let (a, public_key) = ed25519_key(&[0x42; 32]);
+    let mixed = (CompressedEdwardsY(public_key).decompress().unwrap() + EIGHT_TORSION[1]).compress().to_bytes();
+    let mixed_point = CompressedEdwardsY(mixed).decompress().unwrap();
+
+    let r = Scalar::from_bytes_mod_order([0x07; 32]);
+    let r_point = (r * ED25519_BASEPOINT_POINT).compress().to_bytes();
+
+    let mut rejected = 0;
+    for message in [&b"first"[..], b"second", b"third", b"fourth"] {
+        let k = challenge(&r_point, &mixed, message);
+        let signature = signature_bytes(&r_point, &(r + k * a));
+
+        let check = EdwardsPoint::vartime_double_scalar_mul_basepoint(&-k, &mixed_point, &(r + k * a));
+        let r_decoded = CompressedEdwardsY(r_point).decompress().unwrap();
+        assert_eq!(check.mul_by_cofactor(), r_decoded.mul_by_cofactor());
+
+        // sB - kA' = R - (-k mod L)T, which is R only when the reduced -k is a multiple of 8
+        if (-k * EIGHT_TORSION[1]) == EdwardsPoint::identity() {
+            continue;
+        }
+        assert!(!verify_signature_strict(&signature, message, &mixed));
+        rejected += 1;
+    }
+    assert!(rejected > 0);
+}
+
+// u = 0 maps to the point of order 2 and u = 1 to a point of order 4: R = identity and s = 0 verify
+// whenever (-h mod L)A is the identity, which happens for about half and a quarter of all messages
+#[test]
+fn xeddsa_strict_verification_rejects_small_order_keys() {
+    let mut one = [0u8; 32];
+    one[0] = 1;
+
+    for u in [[0u8; 32], one] {
+        let public_key = PublicKey::from(u);
+        let a_point = MontgomeryPoint(u).to_edwards(0).unwrap();
+        assert!(a_point.is_small_order());
+        let a_bytes = a_point.compress().to_bytes();
+
+        let message = (0u32..)
+            .map(|i| i.to_be_bytes())
+            .find(|message| -challenge(&IDENTITY, &a_bytes, message) * a_point == EdwardsPoint::identity())
+            .unwrap();
+        let signature = signature_bytes(&IDENTITY, &Scalar::ZERO);
+
+        assert!(verify(&public_key, &message, &signature));
+        assert!(!verify_strict(&public_key, &message, &signature));
+    }
+}
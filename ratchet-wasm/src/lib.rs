@@ -0,0 +1,155 @@
+use wasm_bindgen::prelude::*;
+use echo_crypto::{EchoError, KeyPair, PrivateKey, PublicKey};
+
+// The Double Ratchet itself lives in echo-crypto, this crate only exposes a session handle to JavaScript
+pub use echo_crypto::ratchet::{MessageHeader, ENCRYPTED_HEADER_LENGTH, HEADER_LENGTH, MAX_SKIP, MAX_SKIPPED_KEYS};
+pub use echo_crypto::session::SESSION_RECORD_VERSION;
+
+// This function copies the X3DH shared secret into a 32 byte key
+fn to_shared_secret(shared_secret: &[u8]) -> Result<[u8; 32], JsValue> {
+    Ok(shared_secret.try_into().map_err(|_| EchoError::InvalidSymmetricKey)?)
+}
+
+// State of one Double Ratchet conversation, see echo_crypto::RatchetSession
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct RatchetSession {
+    inner: echo_crypto::RatchetSession,
+}
+
+impl From<echo_crypto::RatchetSession> for RatchetSession {
+    fn from(inner: echo_crypto::RatchetSession) -> RatchetSession {
+        RatchetSession { inner }
+    }
+}
+
+#[wasm_bindgen]
+impl RatchetSession {
+    // This function starts a session as the initiator using the X3DH shared secret and the responder's signed prekey
+    pub fn initiate(shared_secret: &[u8], remote_ratchet_public: &[u8], associated_data: &[u8]) -> Result<RatchetSession, JsValue> {
+        let shared_secret = to_shared_secret(shared_secret)?;
+        let remote_ratchet_public = PublicKey::from_bytes(remote_ratchet_public)?;
+
+        let inner = echo_crypto::RatchetSession::new_initiator(&shared_secret, &remote_ratchet_public, associated_data)?;
+        Ok(RatchetSession { inner })
+    }
+
+    // This function starts a session as the responder using the X3DH shared secret and our signed prekey private key
+    pub fn respond(shared_secret: &[u8], self_ratchet_private: &[u8], associated_data: &[u8]) -> Result<RatchetSession, JsValue> {
+        let shared_secret = to_shared_secret(shared_secret)?;
+        let private_key = PrivateKey::from_bytes(self_ratchet_private)?;
+        let public_key = private_key.public_key();
+
+        let inner = echo_crypto::RatchetSession::new_responder(&shared_secret, KeyPair { private_key, public_key }, associated_data);
+        Ok(RatchetSession { inner })
+    }
+
+    // This function starts a session as the initiator with header encryption, the ratchet key and counters of every message are encrypted
+    // Both sides must use header encryption, the header keys are derived from the X3DH shared secret
+    pub fn initiate_with_header_encryption(
+        shared_secret: &[u8],
+        remote_ratchet_public: &[u8],
+        associated_data: &[u8],
+    ) -> Result<RatchetSession, JsValue> {
+        let shared_secret = to_shared_secret(shared_secret)?;
+        let remote_ratchet_public = PublicKey::from_bytes(remote_ratchet_public)?;
+
+        let inner = echo_crypto::RatchetSession::new_initiator_with_header_encryption(&shared_secret, &remote_ratchet_public, associated_data)?;
+        Ok(RatchetSession { inner })
+    }
+
+    // This function starts a session as the responder with header encryption
+    pub fn respond_with_header_encryption(
+        shared_secret: &[u8],
+        self_ratchet_private: &[u8],
+        associated_data: &[u8],
+    ) -> Result<RatchetSession, JsValue> {
+        let shared_secret = to_shared_secret(shared_secret)?;
+        let private_key = PrivateKey::from_bytes(self_ratchet_private)?;
+        let public_key = private_key.public_key();
+
+        let inner = echo_crypto::RatchetSession::new_responder_with_header_encryption(
+            &shared_secret,
+            KeyPair { private_key, public_key },
+            associated_data,
+        );
+        Ok(RatchetSession { inner })
+    }
+
+    // This function encrypts the next message of the sending chain
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, JsValue> {
+        self.inner.encrypt(plaintext).map_err(JsValue::from)
+    }
+
+    // This function decrypts a message produced by the other side's encrypt
+    pub fn decrypt(&mut self, message: &[u8]) -> Result<Vec<u8>, JsValue> {
+        self.inner.decrypt(message).map_err(JsValue::from)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn skipped_keys(&self) -> usize {
+        self.inner.skipped_message_key_count()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn header_encryption(&self) -> bool {
+        self.inner.has_header_encryption()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn public_key(&self) -> Vec<u8> {
+        self.inner.ratchet_public_key().to_bytes().to_vec()
+    }
+}
+
+// State of one conversation as it is persisted: the ratchet session and the peer's identity key, see echo_crypto::SessionRecord
+// Use one record per conversation instead of loose per-key localStorage entries
+// Records are only serialized inside a dh-wasm Vault, the plaintext ratchet state is never handed to JavaScript
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct SessionRecord {
+    inner: echo_crypto::SessionRecord,
+}
+
+impl SessionRecord {
+    pub fn as_core(&self) -> &echo_crypto::SessionRecord {
+        &self.inner
+    }
+}
+
+impl From<echo_crypto::SessionRecord> for SessionRecord {
+    fn from(inner: echo_crypto::SessionRecord) -> SessionRecord {
+        SessionRecord { inner }
+    }
+}
+
+#[wasm_bindgen]
+impl SessionRecord {
+    // This function wraps a freshly started session together with the peer's 32 byte identity key
+    #[wasm_bindgen(constructor)]
+    pub fn new(remote_identity: &[u8], session: &RatchetSession) -> Result<SessionRecord, JsValue> {
+        let remote_identity = PublicKey::from_bytes(remote_identity)?;
+        let inner = echo_crypto::SessionRecord::new(remote_identity, session.inner.clone());
+        Ok(SessionRecord { inner })
+    }
+
+    // This function encrypts the next message of the conversation
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, JsValue> {
+        self.inner.encrypt(plaintext).map_err(JsValue::from)
+    }
+
+    // This function decrypts a message, the record is unchanged when it fails
+    pub fn decrypt(&mut self, message: &[u8]) -> Result<Vec<u8>, JsValue> {
+        self.inner.decrypt(message).map_err(JsValue::from)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn remote_identity(&self) -> Vec<u8> {
+        self.inner.remote_identity().to_bytes().to_vec()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn public_key(&self) -> Vec<u8> {
+        self.inner.session().ratchet_public_key().to_bytes().to_vec()
+    }
+}